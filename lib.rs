@@ -10,7 +10,7 @@ mod PeerNftcontract {
 
     use openbrush::{
         contracts::{
-            access_control::{extensions::enumerable::*, only_role},
+            access_control::{extensions::enumerable::*, AccessControlError},
             psp34::{
                 extensions::{
                     burnable::*,
@@ -24,7 +24,9 @@ mod PeerNftcontract {
         traits::{DefaultEnv, Storage, String},
     };
 
-    use ink::prelude::vec::Vec;
+    use ink::storage::{traits::ManualKey, Lazy};
+
+    use ink::prelude::{format, vec::Vec};
     use openbrush::contracts::psp34::balances::BalancesManager;
 
     /// Defines the storage of your contract.
@@ -50,12 +52,842 @@ mod PeerNftcontract {
         /// A unique identifier for the tokens which have been minted (and are therefore
         /// supported) by this contract.
         next_id: u32,
+
+        // Fields added after the first deployment live under their own storage keys, in a
+        // `Lazy` or a `Mapping`, so that the root written by older code still decodes.
+
+        /// Which sale accounts without the MINTER role can mint in
+        sale_phase: Lazy<SalePhase, ManualKey<{ ink::selector_id!("sale_phase") }>>,
+
+        /// Price of a single public mint, paid in the native token
+        mint_price: Lazy<Balance, ManualKey<{ ink::selector_id!("mint_price") }>>,
+
+        /// Maximum number of tokens the contract will ever mint, `0` means uncapped
+        max_supply: Lazy<u32, ManualKey<{ ink::selector_id!("max_supply") }>>,
+
+        /// Maximum number of public mints per account, `0` means uncapped
+        max_mints_per_account: Lazy<u32, ManualKey<{ ink::selector_id!("max_mints_per_account") }>>,
+
+        /// mapping from account to the number of tokens it minted in the public sale
+        public_mints: Mapping<AccountId, u32>,
+
+        /// Native tokens collected by `mint` and not withdrawn yet
+        sale_proceeds: Lazy<Balance, ManualKey<{ ink::selector_id!("sale_proceeds") }>>,
+
+        /// Account receiving the sale proceeds on `withdraw_proceeds`
+        treasury: Lazy<Option<AccountId>, ManualKey<{ ink::selector_id!("treasury") }>>,
+
+        /// mapping from (token id, index) to every `token_uri` the token had, oldest first
+        token_uri_history: Mapping<(Id, u32), TokenUpdate>,
+
+        /// mapping from token id to the length of its `token_uri_history`
+        token_uri_history_len: Mapping<Id, u32>,
+
+        /// mapping from (token id, index) to every `token_location` the token had, oldest first
+        token_location_history: Mapping<(Id, u32), TokenUpdate>,
+
+        /// mapping from token id to the length of its `token_location_history`
+        token_location_history_len: Mapping<Id, u32>,
+
+        /// mapping from token id to its structured location, `token_locations` holds its string form
+        token_geo_locations: Mapping<Id, Location>,
+
+        /// mapping from token id to the geohash of its location, for locations in `"lat,lng"` form
+        token_geohashes: Mapping<Id, String>,
+
+        /// mapping from (geohash prefix, index) to a token located in that cell
+        geohash_tokens: Mapping<(String, u32), Id>,
+
+        /// mapping from geohash prefix to the number of tokens located in that cell
+        geohash_token_count: Mapping<String, u32>,
+
+        /// mapping from (geohash prefix, token id) to the index of the token in `geohash_tokens`
+        geohash_token_index: Mapping<(String, Id), u32>,
+
+        /// Time in milliseconds between `propose_upgrade` and the earliest `execute_upgrade`
+        upgrade_delay: Lazy<Timestamp, ManualKey<{ ink::selector_id!("upgrade_delay") }>>,
+
+        /// Code hash waiting for its delay to pass before it can be set
+        pending_upgrade:
+            Lazy<Option<PendingUpgrade>, ManualKey<{ ink::selector_id!("pending_upgrade") }>>,
+
+        /// Version of the storage layout, `STORAGE_VERSION` once `migrate` has run
+        storage_version: Lazy<u32, ManualKey<{ ink::selector_id!("storage_version") }>>,
+
+        /// Code hashes registered by an ADMIN as eligible for `propose_upgrade`
+        allowed_code_hashes: Mapping<[u8; 32], ()>,
+
+        /// Operations currently halted by a PAUSER
+        paused_operations: Mapping<PausableOperation, ()>,
+
+        /// Royalty applied to tokens without their own royalty
+        default_royalty: Lazy<Option<Royalty>, ManualKey<{ ink::selector_id!("default_royalty") }>>,
+
+        /// mapping from token id to its royalty, overriding `default_royalty`
+        token_royalties: Mapping<Id, Royalty>,
+
+        /// mapping from token id to its fixed-price listing
+        listings: Mapping<Id, Listing>,
+
+        /// Share of marketplace sales kept in `sale_proceeds`, in basis points
+        platform_fee_basis_points:
+            Lazy<u16, ManualKey<{ ink::selector_id!("platform_fee_basis_points") }>>,
+
+        /// mapping from token id to its running auction, the token is held by the contract meanwhile
+        auctions: Mapping<Id, Auction>,
+
+        /// mapping from (token id, bidder) to the bidder's escrowed offer
+        offers: Mapping<(Id, AccountId), Offer>,
+
+        /// mapping from token id to the account renting it, reset on every transfer
+        token_users: Mapping<Id, TokenUser>,
+
+        /// Whether tokens minted from now on are transferable unless stated otherwise,
+        /// stored inverted so that the default is transferable
+        collection_non_transferable:
+            Lazy<bool, ManualKey<{ ink::selector_id!("collection_non_transferable") }>>,
+
+        /// Tokens that were minted non-transferable, they can only be burned
+        non_transferable_tokens: Mapping<Id, ()>,
+
+        /// mapping from token id to the operators its owner approved for it, so that every
+        /// approval can be cleared when the token moves
+        token_approvals: Mapping<Id, Vec<AccountId>>,
+
+        /// Compressed ECDSA public keys allowed to sign mint vouchers
+        voucher_signers: Mapping<[u8; 33], ()>,
+
+        /// Nonces of redeemed mint vouchers
+        used_voucher_nonces: Mapping<u64, ()>,
+
+        /// Root of the Merkle tree of accounts allowed to `presale_mint`
+        presale_merkle_root:
+            Lazy<Option<[u8; 32]>, ManualKey<{ ink::selector_id!("presale_merkle_root") }>>,
+
+        /// Maximum number of presale mints per allowlisted account
+        presale_quota: Lazy<u32, ManualKey<{ ink::selector_id!("presale_quota") }>>,
+
+        /// mapping from account to the number of tokens it minted in the presale
+        presale_mints: Mapping<AccountId, u32>,
+
+        /// Compressed ECDSA public keys of oracles trusted to attest token locations
+        location_oracles: Mapping<[u8; 33], ()>,
+
+        /// mapping from token id to the attestation of its current location, if it has one
+        token_location_attestations: Mapping<Id, LocationAttestation>,
+
+        /// mapping from token id to the timestamp of the latest attestation ever applied to it
+        last_attestation_timestamps: Mapping<Id, Timestamp>,
+
+        /// mapping from geofence id to the geofence registered by a manager
+        geofences: Mapping<u32, Geofence>,
+
+        /// Ids and areas of the registered geofences, evaluated in this order on every location
+        /// change. Kept apart from `geofences` so that matching doesn't decode uris and attributes
+        geofence_areas:
+            Lazy<Vec<(u32, GeofenceArea)>, ManualKey<{ ink::selector_id!("geofence_areas") }>>,
+
+        /// Id given to the next geofence added
+        next_geofence_id: Lazy<u32, ManualKey<{ ink::selector_id!("next_geofence_id") }>>,
+
+        /// mapping from token id to the geofence its current location is in
+        token_geofences: Mapping<Id, u32>,
+    }
+
+    /// Oracle attestation backing the current location of a token.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct LocationAttestation {
+        pub oracle: [u8; 33],
+        /// Time the oracle observed the location at
+        pub timestamp: Timestamp,
+    }
+
+    /// Region of a `Geofence`, coordinates are in degrees multiplied by `10^7` as in `Location`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum GeofenceArea {
+        /// Points within `radius_m` metres of the centre
+        Circle {
+            lat_e7: i32,
+            lng_e7: i32,
+            radius_m: u32,
+        },
+        /// Points between the corners, a box with `min_lng_e7 > max_lng_e7` spans the
+        /// antimeridian
+        BoundingBox {
+            min_lat_e7: i32,
+            min_lng_e7: i32,
+            max_lat_e7: i32,
+            max_lng_e7: i32,
+        },
+    }
+
+    impl GeofenceArea {
+        /// Length of a degree of latitude in metres
+        const METRES_PER_DEGREE: i128 = 111_320;
+        /// Fixed point scale of `cos_e9`
+        const SCALE: i128 = 1_000_000_000;
+        const PI_E9: i128 = 3_141_592_654;
+
+        pub fn is_valid(&self) -> bool {
+            let point = |lat_e7, lng_e7| {
+                Location {
+                    lat_e7,
+                    lng_e7,
+                    altitude_cm: None,
+                    accuracy_m: 0,
+                }
+                .is_valid()
+            };
+            match *self {
+                GeofenceArea::Circle {
+                    lat_e7,
+                    lng_e7,
+                    radius_m,
+                } => point(lat_e7, lng_e7) && radius_m > 0,
+                GeofenceArea::BoundingBox {
+                    min_lat_e7,
+                    min_lng_e7,
+                    max_lat_e7,
+                    max_lng_e7,
+                } => {
+                    point(min_lat_e7, min_lng_e7)
+                        && point(max_lat_e7, max_lng_e7)
+                        && min_lat_e7 <= max_lat_e7
+                }
+            }
+        }
+
+        /// Whether the given coordinates are inside the area.
+        ///
+        /// Circles use an equirectangular approximation, accurate enough for radii of up to a
+        /// few hundred kilometres.
+        pub fn contains(&self, lat_e7: i32, lng_e7: i32) -> bool {
+            match *self {
+                GeofenceArea::Circle {
+                    lat_e7: center_lat_e7,
+                    lng_e7: center_lng_e7,
+                    radius_m,
+                } => {
+                    let d_lat = lat_e7 as i128 - center_lat_e7 as i128;
+                    let mut d_lng = (lng_e7 as i128 - center_lng_e7 as i128).abs();
+                    if d_lng > 1_800_000_000 {
+                        d_lng = 3_600_000_000 - d_lng;
+                    }
+                    let mean_lat_e7 = ((lat_e7 as i64 + center_lat_e7 as i64) / 2) as i32;
+
+                    // Distances in metres multiplied by 10^7
+                    let dy = d_lat * Self::METRES_PER_DEGREE;
+                    let dx =
+                        d_lng * Self::METRES_PER_DEGREE * Self::cos_e9(mean_lat_e7) / Self::SCALE;
+                    let radius = radius_m as i128 * 10_000_000;
+                    dx * dx + dy * dy <= radius * radius
+                }
+                GeofenceArea::BoundingBox {
+                    min_lat_e7,
+                    min_lng_e7,
+                    max_lat_e7,
+                    max_lng_e7,
+                } => {
+                    let in_lng = if min_lng_e7 <= max_lng_e7 {
+                        (min_lng_e7..=max_lng_e7).contains(&lng_e7)
+                    } else {
+                        lng_e7 >= min_lng_e7 || lng_e7 <= max_lng_e7
+                    };
+                    (min_lat_e7..=max_lat_e7).contains(&lat_e7) && in_lng
+                }
+            }
+        }
+
+        /// Cosine of a latitude scaled by `SCALE`, from its Taylor series since contracts
+        /// can't use floating point.
+        fn cos_e9(lat_e7: i32) -> i128 {
+            let x = lat_e7 as i128 * Self::PI_E9 / 1_800_000_000;
+            let x2 = x * x / Self::SCALE;
+            let x4 = x2 * x2 / Self::SCALE;
+            let x6 = x4 * x2 / Self::SCALE;
+            (Self::SCALE - x2 / 2 + x4 / 24 - x6 / 720).max(0)
+        }
+    }
+
+    /// Region whose token uri and attributes are applied to tokens moving into it.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Geofence {
+        pub area: GeofenceArea,
+        pub token_uri: String,
+        /// `(key, value)` attributes set on entering tokens
+        pub attributes: Vec<(String, String)>,
+    }
+
+    /// Sale accounts without the MINTER role can currently mint in.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum SalePhase {
+        #[default]
+        Closed,
+        /// `mint` is open to everybody
+        Public,
+        /// Only allowlisted accounts can mint, with `presale_mint`
+        Presale,
+    }
+
+    /// Off-chain signed permission to mint one token, redeemed with `redeem_voucher`.
+    ///
+    /// A registered signer signs the Blake2x256 hash of the SCALE encoded
+    /// `(contract AccountId, MintVoucher)` with ECDSA secp256k1.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct MintVoucher {
+        pub recipient: AccountId,
+        pub token_uri: String,
+        pub token_location: String,
+        /// Value the redeemer has to transfer
+        pub price: Balance,
+        /// Unique per voucher, a nonce can be redeemed only once
+        pub nonce: u64,
+        /// The voucher can't be redeemed from this timestamp on
+        pub expiry: Timestamp,
+    }
+
+    /// Renter of a token set with `set_user`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct TokenUser {
+        pub user: AccountId,
+        /// The user loses its rights from this timestamp on
+        pub expires: Timestamp,
+    }
+
+    /// An escrowed offer made with `make_offer`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Offer {
+        pub amount: Balance,
+        /// The offer can't be accepted from this timestamp on
+        pub expiry: Timestamp,
+    }
+
+    /// An English auction started with `create_auction`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Auction {
+        pub seller: AccountId,
+        /// Lowest accepted first bid
+        pub reserve_price: Balance,
+        /// Amount every bid has to add to the highest bid
+        pub min_bid_increment: Balance,
+        pub end_time: Timestamp,
+        pub highest_bidder: Option<AccountId>,
+        /// Escrowed by the contract until the bid is outbid or the auction is settled
+        pub highest_bid: Balance,
+    }
+
+    /// A token offered for sale with `list`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Listing {
+        pub seller: AccountId,
+        pub price: Balance,
+    }
+
+    /// Share of secondary sales paid to `receiver`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Royalty {
+        pub receiver: AccountId,
+        /// Share of the sale price in basis points, `10_000` is 100%
+        pub basis_points: u16,
+    }
+
+    impl Royalty {
+        /// Royalty amount for `sale_price`, rounded down.
+        pub fn amount(&self, sale_price: Balance) -> Balance {
+            basis_points_of(sale_price, self.basis_points)
+        }
+    }
+
+    /// Leaf of `account` in the presale Merkle tree, Blake2x256 of its SCALE encoding.
+    pub fn presale_leaf(account: &AccountId) -> [u8; 32] {
+        let mut leaf = [0u8; 32];
+        ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(account, &mut leaf);
+        leaf
+    }
+
+    /// Verifies `leaf` is in the tree of `root`. Pairs are hashed sorted, so the proof doesn't
+    /// need to say on which side each sibling is.
+    pub fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+        let computed = proof
+            .iter()
+            .fold(leaf, |node, sibling| hash_merkle_pair(node, *sibling));
+        computed == root
+    }
+
+    fn hash_merkle_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        let (first, second) = if a <= b { (a, b) } else { (b, a) };
+        let mut node = [0u8; 32];
+        ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(&(first, second), &mut node);
+        node
+    }
+
+    /// `basis_points` of `amount`, rounded down, without overflowing.
+    fn basis_points_of(amount: Balance, basis_points: u16) -> Balance {
+        let basis_points = basis_points as Balance;
+        let max = MAX_ROYALTY_BASIS_POINTS as Balance;
+        (amount / max) * basis_points + (amount % max) * basis_points / max
+    }
+
+    /// Groups of messages a PAUSER can halt independently.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PausableOperation {
+        /// `mint`, `manager_mint` and `manager_batch_mint`
+        Mint,
+        /// PSP34 `transfer`, marketplace sales and `recovery_transfer`
+        Transfer,
+        /// PSP34 `burn` and `manager_burn`
+        Burn,
+        /// Token uri and location updates
+        Update,
+    }
+
+    impl PausableOperation {
+        pub const ALL: [PausableOperation; 4] = [
+            PausableOperation::Mint,
+            PausableOperation::Transfer,
+            PausableOperation::Burn,
+            PausableOperation::Update,
+        ];
+    }
+
+    /// An upgrade proposed with `propose_upgrade`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct PendingUpgrade {
+        pub code_hash: [u8; 32],
+        pub executable_at: Timestamp,
+    }
+
+    /// A value a token's uri or location was set to.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct TokenUpdate {
+        pub value: String,
+        pub block_number: BlockNumber,
+        pub timestamp: Timestamp,
+        pub updater: AccountId,
+    }
+
+    /// Geographic location of a token.
+    ///
+    /// Coordinates are stored in degrees multiplied by `10^7`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Location {
+        pub lat_e7: i32,
+        pub lng_e7: i32,
+        pub altitude_cm: Option<i32>,
+        pub accuracy_m: u32,
+    }
+
+    impl Location {
+        const MAX_LAT_E7: i32 = 900_000_000;
+        const MAX_LNG_E7: i32 = 1_800_000_000;
+
+        pub fn is_valid(&self) -> bool {
+            (-Self::MAX_LAT_E7..=Self::MAX_LAT_E7).contains(&self.lat_e7)
+                && (-Self::MAX_LNG_E7..=Self::MAX_LNG_E7).contains(&self.lng_e7)
+        }
+
+        /// String form stored in `token_locations`, `"lat,lng"` in decimal degrees.
+        pub fn to_location_string(&self) -> String {
+            format!(
+                "{},{}",
+                Self::format_e7(self.lat_e7),
+                Self::format_e7(self.lng_e7)
+            )
+            .into_bytes()
+        }
+
+        fn format_e7(value: i32) -> ink::prelude::string::String {
+            let sign = if value < 0 { "-" } else { "" };
+            let value = value.unsigned_abs();
+            format!("{}{}.{:07}", sign, value / 10_000_000, value % 10_000_000)
+        }
+
+        /// Parses the `"lat,lng"` form produced by `to_location_string`.
+        ///
+        /// Returns `(lat_e7, lng_e7)`, or `None` if the string isn't a valid coordinate pair.
+        pub fn parse_coordinates(location: &str) -> Option<(i32, i32)> {
+            let (lat, lng) = location.split_once(',')?;
+            let location = Location {
+                lat_e7: Self::parse_e7(lat)?,
+                lng_e7: Self::parse_e7(lng)?,
+                altitude_cm: None,
+                accuracy_m: 0,
+            };
+            if !location.is_valid() {
+                return None;
+            }
+            Some((location.lat_e7, location.lng_e7))
+        }
+
+        fn parse_e7(value: &str) -> Option<i32> {
+            let value = value.trim();
+            let (negative, value) = match value.strip_prefix('-') {
+                Some(value) => (true, value),
+                None => (false, value),
+            };
+            let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+            if integer.is_empty()
+                || fraction.len() > 7
+                || !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+            {
+                return None;
+            }
+
+            let mut result: i64 = integer.parse().ok()?;
+            for position in 0..7 {
+                let digit = fraction.as_bytes().get(position).map_or(0, |b| b - b'0');
+                result = result.checked_mul(10)?.checked_add(digit as i64)?;
+            }
+            if negative {
+                result = -result;
+            }
+            i32::try_from(result).ok()
+        }
+
+        /// Geohash of the given coordinates with `precision` characters.
+        pub fn geohash(lat_e7: i32, lng_e7: i32, precision: usize) -> String {
+            const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+            let (mut lat_min, mut lat_max) = (-(Self::MAX_LAT_E7 as i64), Self::MAX_LAT_E7 as i64);
+            let (mut lng_min, mut lng_max) = (-(Self::MAX_LNG_E7 as i64), Self::MAX_LNG_E7 as i64);
+            let mut hash = String::new();
+            let mut even_bit = true;
+
+            for _ in 0..precision {
+                let mut index = 0;
+                for _ in 0..5 {
+                    let (value, min, max) = if even_bit {
+                        (lng_e7 as i64, &mut lng_min, &mut lng_max)
+                    } else {
+                        (lat_e7 as i64, &mut lat_min, &mut lat_max)
+                    };
+                    let mid = (*min + *max) / 2;
+                    index <<= 1;
+                    if value >= mid {
+                        index |= 1;
+                        *min = mid;
+                    } else {
+                        *max = mid;
+                    }
+                    even_bit = !even_bit;
+                }
+                hash.push(BASE32[index]);
+            }
+            hash
+        }
     }
 
     const MANAGER: RoleType = ink::selector_id!("MANAGER");
-    // const MINTER: RoleType = ink::selector_id!("MINTER");
+    const MINTER: RoleType = ink::selector_id!("MINTER");
+    const MINTER_ADMIN: RoleType = ink::selector_id!("MINTER_ADMIN");
+    const ADMIN: RoleType = ink::selector_id!("ADMIN");
+    const PAUSER: RoleType = ink::selector_id!("PAUSER");
+    const RECOVERY: RoleType = ink::selector_id!("RECOVERY");
     // const BURNER: RoleType = ink::selector_id!("BURNER");
 
+    /// Maximum length in bytes of a `token_uri`
+    const MAX_TOKEN_URI_LEN: usize = 512;
+    /// Maximum length in bytes of a `token_location`
+    const MAX_TOKEN_LOCATION_LEN: usize = 256;
+    /// Maximum number of items returned by a paginated query
+    const MAX_PAGE_SIZE: u32 = 100;
+    /// Maximum number of tokens minted by a single `manager_batch_mint`
+    const MAX_BATCH_SIZE: u32 = 50;
+    /// Maximum number of tokens updated by a single batch update message, each update also
+    /// records history, reindexes geohashes and evaluates geofences
+    const MAX_UPDATE_BATCH_SIZE: u32 = 50;
+    /// Storage layout version this code works with
+    ///
+    /// `0` is the layout before versioning was introduced. Bump it whenever an upgrade needs
+    /// `migrate` to transform existing storage, and add the step to `migrate`.
+    const STORAGE_VERSION: u32 = 1;
+    /// Highest royalty in basis points, 100% of the sale price
+    const MAX_ROYALTY_BASIS_POINTS: u16 = 10_000;
+    /// Upgrade delay set by the constructor, 2 days
+    const DEFAULT_UPGRADE_DELAY: Timestamp = 2 * 24 * 60 * 60 * 1000;
+    /// Lowest upgrade delay a manager can set, 1 day
+    const MIN_UPGRADE_DELAY: Timestamp = 24 * 60 * 60 * 1000;
+    /// Longest auction `create_auction` accepts, 30 days
+    const MAX_AUCTION_DURATION: Timestamp = 30 * 24 * 60 * 60 * 1000;
+    /// Bids placed this close to the end of an auction extend it to this far after the bid,
+    /// 10 minutes
+    const AUCTION_EXTENSION: Timestamp = 10 * 60 * 1000;
+    /// Maximum number of operators approved for a single token
+    const MAX_TOKEN_APPROVALS: usize = 16;
+    /// Oldest attestation `submit_location_attestation` accepts, 1 hour
+    const MAX_ATTESTATION_AGE: Timestamp = 60 * 60 * 1000;
+    /// Number of geohash characters indexed per token, 6 is a cell of roughly 1.2km x 0.6km
+    const GEOHASH_PRECISION: usize = 6;
+    /// Maximum number of registered geofences, all of them are evaluated on a location change
+    const MAX_GEOFENCES: usize = 32;
+    /// Maximum number of attributes a geofence sets
+    const MAX_GEOFENCE_ATTRIBUTES: usize = 8;
+    /// Maximum length in bytes of the key and of the value of a geofence attribute
+    const MAX_GEOFENCE_ATTRIBUTE_LEN: usize = 64;
+
+    /// Errors returned by the messages of `PeerNftcontract`.
+    ///
+    /// Variants are encoded by position, append new ones at the end so that upgrades keep the
+    /// existing indices.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PeerNftError {
+        /// The token with the given id doesn't exist
+        TokenNotFound,
+        /// Caller doesn't have the MANAGER role
+        NotManager,
+        /// Caller doesn't have the MINTER role and the public sale is closed
+        NotMinter,
+        /// Caller is neither the owner of the token nor approved for it
+        NotApproved,
+        /// `max_supply` tokens have already been minted
+        SupplyExhausted,
+        /// `max_supply` can't be lowered below the number of minted tokens
+        SupplyBelowMinted,
+        /// Transferred value doesn't match `mint_price`
+        IncorrectPayment,
+        /// Caller reached `max_mints_per_account`
+        MintLimitReached,
+        /// `token_uri` is longer than `MAX_TOKEN_URI_LEN`
+        UriTooLong,
+        /// `token_location` is longer than `MAX_TOKEN_LOCATION_LEN`
+        LocationTooLong,
+        /// No treasury account has been set
+        TreasuryNotSet,
+        /// Transfer of native tokens failed
+        TransferFailed,
+        /// `set_code_hash` failed
+        UpgradeFailed,
+        PSP34Error(PSP34Error),
+        AccessControlError(AccessControlError),
+        /// Batch is empty or larger than the maximum batch size
+        InvalidBatchSize,
+        /// Latitude or longitude of a `Location` is out of range
+        InvalidLocation,
+        /// An upgrade is already pending, cancel it first
+        UpgradeAlreadyPending,
+        /// No upgrade has been proposed
+        NoPendingUpgrade,
+        /// The upgrade delay of the pending upgrade hasn't passed yet
+        UpgradeNotReady,
+        /// Upgrade delay is lower than `MIN_UPGRADE_DELAY`
+        UpgradeDelayTooShort,
+        /// Storage has to be migrated to `STORAGE_VERSION` with `migrate` first
+        MigrationRequired,
+        /// `from_version` passed to `migrate` doesn't match the stored version
+        InvalidStorageVersion,
+        /// Caller doesn't have the ADMIN role
+        NotAdmin,
+        /// Code hash hasn't been registered by an ADMIN
+        CodeHashNotAllowed,
+        /// Caller doesn't have the PAUSER role
+        NotPauser,
+        /// The operation is paused
+        Paused,
+        /// Royalty basis points are above `MAX_ROYALTY_BASIS_POINTS`
+        InvalidRoyalty,
+        /// Caller doesn't own the token
+        NotTokenOwner,
+        /// Price must be greater than zero
+        InvalidPrice,
+        /// The token isn't listed for sale
+        NotListed,
+        /// Royalty and platform fee together exceed the sale price
+        FeesExceedPrice,
+        /// Platform fee is above `MAX_ROYALTY_BASIS_POINTS`
+        InvalidFee,
+        /// The token has no running auction
+        AuctionNotFound,
+        /// The token is held by a running auction
+        TokenInAuction,
+        /// Auction duration is zero or longer than `MAX_AUCTION_DURATION`
+        InvalidDuration,
+        /// The auction has ended, it can only be settled
+        AuctionEnded,
+        /// The auction hasn't ended yet
+        AuctionNotEnded,
+        /// The auction already has bids and can't be cancelled
+        AuctionHasBids,
+        /// Bid is below the reserve price or the minimum increment
+        BidTooLow,
+        /// No offer from this bidder on the token
+        OfferNotFound,
+        /// The offer has expired
+        OfferExpired,
+        /// The token was minted non-transferable
+        NotTransferable,
+        /// Caller doesn't have the RECOVERY role
+        NotRecovery,
+        /// `recovery_transfer` needs a non-zero reason code
+        MissingReason,
+        /// Signature can't be recovered
+        InvalidSignature,
+        /// Voucher was signed by a key that isn't a registered signer
+        UnknownSigner,
+        /// The voucher has expired
+        VoucherExpired,
+        /// The voucher nonce was already redeemed
+        VoucherAlreadyUsed,
+        /// The presale isn't running
+        PresaleClosed,
+        /// Caller isn't in the presale allowlist
+        NotAllowlisted,
+        /// Attestation was signed by a key that isn't a registered location oracle
+        UnknownOracle,
+        /// Attestation is older than `MAX_ATTESTATION_AGE`, from the future, or not newer than
+        /// the latest attestation of the token
+        StaleAttestation,
+        /// Area of a geofence is out of range or empty, or it sets too many or too long attributes
+        InvalidGeofence,
+        /// `MAX_GEOFENCES` geofences are already registered
+        TooManyGeofences,
+        /// No geofence with the given id is registered
+        GeofenceNotFound,
+        /// The seller can't bid on their own auction
+        SellerCannotBid,
+        /// The owner can't make an offer on their own token
+        SelfOffer,
+    }
+
+    impl From<PSP34Error> for PeerNftError {
+        fn from(error: PSP34Error) -> Self {
+            PeerNftError::PSP34Error(error)
+        }
+    }
+
+    impl From<AccessControlError> for PeerNftError {
+        fn from(error: AccessControlError) -> Self {
+            PeerNftError::AccessControlError(error)
+        }
+    }
+
+    /// Throws `PeerNftError::NotManager` if the caller doesn't have the MANAGER role.
+    #[openbrush::modifier_definition]
+    pub fn only_manager<F, R>(instance: &mut PeerNftcontract, body: F) -> Result<R, PeerNftError>
+    where
+        F: FnOnce(&mut PeerNftcontract) -> Result<R, PeerNftError>,
+    {
+        if !instance.has_role(MANAGER, PeerNftcontract::env().caller()) {
+            return Err(PeerNftError::NotManager);
+        }
+        body(instance)
+    }
+
+    /// Throws `PeerNftError::NotAdmin` if the caller doesn't have the ADMIN role.
+    #[openbrush::modifier_definition]
+    pub fn only_admin<F, R>(instance: &mut PeerNftcontract, body: F) -> Result<R, PeerNftError>
+    where
+        F: FnOnce(&mut PeerNftcontract) -> Result<R, PeerNftError>,
+    {
+        if !instance.has_role(ADMIN, PeerNftcontract::env().caller()) {
+            return Err(PeerNftError::NotAdmin);
+        }
+        body(instance)
+    }
+
+    /// Throws `PeerNftError::NotPauser` if the caller doesn't have the PAUSER role.
+    #[openbrush::modifier_definition]
+    pub fn only_pauser<F, R>(instance: &mut PeerNftcontract, body: F) -> Result<R, PeerNftError>
+    where
+        F: FnOnce(&mut PeerNftcontract) -> Result<R, PeerNftError>,
+    {
+        if !instance.has_role(PAUSER, PeerNftcontract::env().caller()) {
+            return Err(PeerNftError::NotPauser);
+        }
+        body(instance)
+    }
+
+    /// Throws `PeerNftError::NotRecovery` if the caller doesn't have the RECOVERY role.
+    #[openbrush::modifier_definition]
+    pub fn only_recovery<F, R>(instance: &mut PeerNftcontract, body: F) -> Result<R, PeerNftError>
+    where
+        F: FnOnce(&mut PeerNftcontract) -> Result<R, PeerNftError>,
+    {
+        if !instance.has_role(RECOVERY, PeerNftcontract::env().caller()) {
+            return Err(PeerNftError::NotRecovery);
+        }
+        body(instance)
+    }
+
+    /// Throws `PeerNftError::Paused` if `operation` is paused.
+    #[openbrush::modifier_definition]
+    pub fn when_not_paused<F, R>(
+        instance: &mut PeerNftcontract,
+        body: F,
+        operation: PausableOperation,
+    ) -> Result<R, PeerNftError>
+    where
+        F: FnOnce(&mut PeerNftcontract) -> Result<R, PeerNftError>,
+    {
+        if instance.is_paused(operation) {
+            return Err(PeerNftError::Paused);
+        }
+        body(instance)
+    }
+
+    /// Throws `PeerNftError::MigrationRequired` if the storage hasn't been migrated to
+    /// `STORAGE_VERSION` yet.
+    #[openbrush::modifier_definition]
+    pub fn when_migrated<F, R>(instance: &mut PeerNftcontract, body: F) -> Result<R, PeerNftError>
+    where
+        F: FnOnce(&mut PeerNftcontract) -> Result<R, PeerNftError>,
+    {
+        if instance.storage_version.get().unwrap_or_default() != STORAGE_VERSION {
+            return Err(PeerNftError::MigrationRequired);
+        }
+        body(instance)
+    }
+
     // Section contains default implementation without any modifications
     impl PSP34 for PeerNftcontract {}
     impl AccessControl for PeerNftcontract {}
@@ -66,15 +898,19 @@ mod PeerNftcontract {
     impl PSP34Burnable for PeerNftcontract {
         #[ink(message)]
         fn burn(&mut self, account: AccountId, id: Id) -> Result<(), PSP34Error> {
-            let owner = self.owner_of(id.clone()).unwrap();
+            let owner = self.owner_of(id.clone()).ok_or(PSP34Error::TokenNotExists)?;
             let caller = Self::env().caller();
 
+            // `_burn_from` trusts `account`, a wrong one would corrupt the enumerable index
+            if account != owner {
+                return Err(PSP34Error::Custom(String::from("NotTokenOwner")));
+            }
             if owner != caller && !self._allowance(&owner, &caller, &Some(&id)) {
                 return Err(PSP34Error::NotApproved);
             }
-            self.remove_token_uri(id.clone());
-            self.remove_token_location(id.clone());
-            self._burn_from(account, id)
+            self._burn_from(account, id.clone())?;
+            self._clear_burned_token(id);
+            Ok(())
         }
     }
 
@@ -110,6 +946,22 @@ mod PeerNftcontract {
         _token_uri: String,
     }
 
+    /// Event emitted when a update_token_uri occurs.
+    #[ink(event)]
+    pub struct UpdateTokenUri {
+        #[ink(topic)]
+        _id: Id,
+        _old_token_uri: Option<String>,
+        _token_uri: String,
+    }
+
+    /// Event emitted when a token uri is removed.
+    #[ink(event)]
+    pub struct RemoveTokenUri {
+        #[ink(topic)]
+        _id: Id,
+    }
+
     /// Event emitted when a set_token_location occurs.
     #[ink(event)]
     pub struct SetTokenLocation {
@@ -119,97 +971,491 @@ mod PeerNftcontract {
         _token_location: String,
     }
 
-    /// Event emitted when a attribute_set occurs.
+    /// Event emitted when a update_token_location occurs.
     #[ink(event)]
-    pub struct SetAttribute {
+    pub struct UpdateTokenLocation {
         #[ink(topic)]
         _id: Id,
+        _old_token_location: Option<String>,
+        _token_location: String,
+    }
+
+    /// Event emitted when a token location is removed.
+    #[ink(event)]
+    pub struct RemoveTokenLocation {
         #[ink(topic)]
-        _key: String,
+        _id: Id,
+    }
+
+    /// Event emitted when a propose_upgrade occurs.
+    #[ink(event)]
+    pub struct UpgradeProposed {
         #[ink(topic)]
-        _data: String,
+        _code_hash: [u8; 32],
+        _executable_at: Timestamp,
     }
 
-    /// Event emitted when a role admin changed occurs.
+    /// Event emitted when a execute_upgrade occurs.
     #[ink(event)]
-    pub struct RoleAdminChanged {
+    pub struct UpgradeExecuted {
         #[ink(topic)]
-        _role: RoleType,
+        _code_hash: [u8; 32],
+    }
+
+    /// Event emitted when a cancel_upgrade occurs.
+    #[ink(event)]
+    pub struct UpgradeCancelled {
         #[ink(topic)]
-        _previous: RoleType,
+        _code_hash: [u8; 32],
+    }
+
+    /// Event emitted when a set_upgrade_delay occurs.
+    #[ink(event)]
+    pub struct UpgradeDelayChanged {
+        _previous: Timestamp,
+        _new: Timestamp,
+    }
+
+    /// Event emitted when a register_code_hash occurs.
+    #[ink(event)]
+    pub struct CodeHashRegistered {
         #[ink(topic)]
-        _new: RoleType,
+        _code_hash: [u8; 32],
     }
 
-    /// Event emitted when a role grant occurs.
+    /// Event emitted when a deregister_code_hash occurs.
     #[ink(event)]
-    pub struct RoleGranted {
+    pub struct CodeHashDeregistered {
         #[ink(topic)]
-        _role: RoleType,
+        _code_hash: [u8; 32],
+    }
+
+    /// Event emitted when a pause occurs.
+    #[ink(event)]
+    pub struct Paused {
         #[ink(topic)]
-        _grantee: AccountId,
+        _operation: PausableOperation,
         #[ink(topic)]
-        _grantor:Option<AccountId>,
+        _account: AccountId,
     }
 
-    /// Event emitted when a role revoked occurs.
+    /// Event emitted when a unpause occurs.
     #[ink(event)]
-    pub struct RoleRevoked {
+    pub struct Unpaused {
         #[ink(topic)]
-        _role: RoleType,
+        _operation: PausableOperation,
         #[ink(topic)]
         _account: AccountId,
-        #[ink(topic)]
-        _sender: AccountId,
     }
 
-    // Override event emission methods
-    impl access_control::Internal for PeerNftcontract {
-        default fn _emit_role_admin_changed(
-            &mut self,
-            _role: RoleType,
-            _previous: RoleType,
-            _new: RoleType,
-        ) {
-            self.env().emit_event(RoleAdminChanged {
-                _role,
-                _previous,
-                _new,
-            });
-        }
-        default fn _emit_role_granted(
-            &mut self,
-            _role: RoleType,
-            _grantee: AccountId,
-            _grantor: Option<AccountId>,
-        ) {
-            self.env().emit_event(RoleGranted {
-                _role,
-                _grantee,
-                _grantor,
-            });
-        }
-        default fn _emit_role_revoked(
-            &mut self,
-            _role: RoleType,
-            _account: AccountId,
-            _sender: AccountId,
-        ) {
-            self.env().emit_event(RoleRevoked {
-                _role,
-                _account,
-                _sender,
-            });
-        }
+    /// Event emitted when a royalty is set or removed, `_id` is `None` for the collection default.
+    #[ink(event)]
+    pub struct RoyaltySet {
+        #[ink(topic)]
+        _id: Option<Id>,
+        _royalty: Option<Royalty>,
     }
 
-    // Override event emission methods
-    impl psp34::Internal for PeerNftcontract {
-        fn _emit_transfer_event(&self, from: Option<AccountId>, to: Option<AccountId>, id: Id) {
-            self.env().emit_event(Transfer { from, to, id });
-        }
+    /// Event emitted when a set_platform_fee occurs.
+    #[ink(event)]
+    pub struct PlatformFeeSet {
+        _basis_points: u16,
+    }
 
-        fn _emit_approval_event(
+    /// Event emitted when a list occurs.
+    #[ink(event)]
+    pub struct Listed {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _seller: AccountId,
+        _price: Balance,
+    }
+
+    /// Event emitted when a cancel_listing occurs.
+    #[ink(event)]
+    pub struct ListingCancelled {
+        #[ink(topic)]
+        _id: Id,
+    }
+
+    /// Event emitted when a buy occurs.
+    #[ink(event)]
+    pub struct Sold {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _seller: AccountId,
+        #[ink(topic)]
+        _buyer: AccountId,
+        _price: Balance,
+    }
+
+    /// Event emitted when a create_auction occurs.
+    #[ink(event)]
+    pub struct AuctionCreated {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _seller: AccountId,
+        _reserve_price: Balance,
+        _end_time: Timestamp,
+    }
+
+    /// Event emitted when a bid occurs.
+    #[ink(event)]
+    pub struct BidPlaced {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _bidder: AccountId,
+        _amount: Balance,
+        _end_time: Timestamp,
+    }
+
+    /// Event emitted when a settle_auction occurs, `_winner` is `None` if nobody bid.
+    #[ink(event)]
+    pub struct AuctionSettled {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _winner: Option<AccountId>,
+        _amount: Balance,
+    }
+
+    /// Event emitted when a cancel_auction occurs.
+    #[ink(event)]
+    pub struct AuctionCancelled {
+        #[ink(topic)]
+        _id: Id,
+    }
+
+    /// Event emitted when a make_offer occurs, `_amount` is the total escrowed by the bidder.
+    #[ink(event)]
+    pub struct OfferMade {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _bidder: AccountId,
+        _amount: Balance,
+        _expiry: Timestamp,
+    }
+
+    /// Event emitted when a withdraw_offer occurs.
+    #[ink(event)]
+    pub struct OfferWithdrawn {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _bidder: AccountId,
+    }
+
+    /// Event emitted when a accept_offer occurs.
+    #[ink(event)]
+    pub struct OfferAccepted {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _seller: AccountId,
+        #[ink(topic)]
+        _bidder: AccountId,
+        _amount: Balance,
+    }
+
+    /// Event emitted when the user of a token changes, `_user` is `None` when it is reset.
+    #[ink(event)]
+    pub struct UpdateUser {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _user: Option<AccountId>,
+        _expires: Timestamp,
+    }
+
+    /// Event emitted when the transferable flag is set, `_id` is `None` for the collection default.
+    #[ink(event)]
+    pub struct TransferableSet {
+        #[ink(topic)]
+        _id: Option<Id>,
+        _transferable: bool,
+    }
+
+    /// Event emitted when a recovery_transfer occurs.
+    #[ink(event)]
+    pub struct ForcedTransfer {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _from: AccountId,
+        #[ink(topic)]
+        _to: AccountId,
+        _operator: AccountId,
+        _reason: u32,
+    }
+
+    /// Event emitted when a voucher signer is added or removed.
+    #[ink(event)]
+    pub struct VoucherSignerSet {
+        _signer: [u8; 33],
+        _allowed: bool,
+    }
+
+    /// Event emitted when a redeem_voucher occurs.
+    #[ink(event)]
+    pub struct VoucherRedeemed {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _recipient: AccountId,
+        _nonce: u64,
+    }
+
+    /// Event emitted when a location oracle is added or removed.
+    #[ink(event)]
+    pub struct LocationOracleSet {
+        _oracle: [u8; 33],
+        _allowed: bool,
+    }
+
+    /// Event emitted when a submit_location_attestation occurs.
+    #[ink(event)]
+    pub struct LocationAttested {
+        #[ink(topic)]
+        _id: Id,
+        _oracle: [u8; 33],
+        _timestamp: Timestamp,
+    }
+
+    /// Event emitted when a add_geofence occurs.
+    #[ink(event)]
+    pub struct GeofenceAdded {
+        #[ink(topic)]
+        _fence_id: u32,
+        _geofence: Geofence,
+    }
+
+    /// Event emitted when a remove_geofence occurs.
+    #[ink(event)]
+    pub struct GeofenceRemoved {
+        #[ink(topic)]
+        _fence_id: u32,
+    }
+
+    /// Event emitted when a token moves into a geofence.
+    #[ink(event)]
+    pub struct GeofenceEntered {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _fence_id: u32,
+    }
+
+    /// Event emitted when a token moves out of a geofence.
+    #[ink(event)]
+    pub struct GeofenceExited {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _fence_id: u32,
+    }
+
+    /// Event emitted when a migrate occurs.
+    #[ink(event)]
+    pub struct StorageMigrated {
+        _from_version: u32,
+        _to_version: u32,
+    }
+
+    /// Event emitted when a attribute_set occurs.
+    #[ink(event)]
+    pub struct SetAttribute {
+        #[ink(topic)]
+        _id: Id,
+        #[ink(topic)]
+        _key: String,
+        #[ink(topic)]
+        _data: String,
+    }
+
+    /// Event emitted when a role admin changed occurs.
+    #[ink(event)]
+    pub struct RoleAdminChanged {
+        #[ink(topic)]
+        _role: RoleType,
+        #[ink(topic)]
+        _previous: RoleType,
+        #[ink(topic)]
+        _new: RoleType,
+    }
+
+    /// Event emitted when a role grant occurs.
+    #[ink(event)]
+    pub struct RoleGranted {
+        #[ink(topic)]
+        _role: RoleType,
+        #[ink(topic)]
+        _grantee: AccountId,
+        #[ink(topic)]
+        _grantor:Option<AccountId>,
+    }
+
+    /// Event emitted when a role revoked occurs.
+    #[ink(event)]
+    pub struct RoleRevoked {
+        #[ink(topic)]
+        _role: RoleType,
+        #[ink(topic)]
+        _account: AccountId,
+        #[ink(topic)]
+        _sender: AccountId,
+    }
+
+    // Override event emission methods
+    impl access_control::Internal for PeerNftcontract {
+        default fn _emit_role_admin_changed(
+            &mut self,
+            _role: RoleType,
+            _previous: RoleType,
+            _new: RoleType,
+        ) {
+            self.env().emit_event(RoleAdminChanged {
+                _role,
+                _previous,
+                _new,
+            });
+        }
+        default fn _emit_role_granted(
+            &mut self,
+            _role: RoleType,
+            _grantee: AccountId,
+            _grantor: Option<AccountId>,
+        ) {
+            self.env().emit_event(RoleGranted {
+                _role,
+                _grantee,
+                _grantor,
+            });
+        }
+        default fn _emit_role_revoked(
+            &mut self,
+            _role: RoleType,
+            _account: AccountId,
+            _sender: AccountId,
+        ) {
+            self.env().emit_event(RoleRevoked {
+                _role,
+                _account,
+                _sender,
+            });
+        }
+    }
+
+    // Transfer hooks, openbrush calls them around every mint, transfer and burn
+    impl psp34::Transfer for PeerNftcontract {
+        fn _before_token_transfer(
+            &mut self,
+            _from: Option<&AccountId>,
+            _to: Option<&AccountId>,
+            _id: &Id,
+        ) -> Result<(), PSP34Error> {
+            if self.storage_version.get().unwrap_or_default() != STORAGE_VERSION {
+                return Err(PSP34Error::Custom(String::from("MigrationRequired")));
+            }
+            let operation = match (_from, _to) {
+                (None, _) => PausableOperation::Mint,
+                (_, None) => PausableOperation::Burn,
+                _ => PausableOperation::Transfer,
+            };
+            if self.is_paused(operation) {
+                return Err(PSP34Error::Custom(String::from("Paused")));
+            }
+            if operation == PausableOperation::Transfer && !self.is_transferable(_id.clone()) {
+                return Err(PSP34Error::Custom(String::from("NotTransferable")));
+            }
+            Ok(())
+        }
+
+        fn _after_token_transfer(
+            &mut self,
+            _from: Option<&AccountId>,
+            _to: Option<&AccountId>,
+            _id: &Id,
+        ) -> Result<(), PSP34Error> {
+            if let Some(from) = _from {
+                self._clear_token_approvals(from, _id);
+            }
+            // A listing is only valid for the owner who created it
+            self.listings.remove(_id);
+            // Rentals are granted by an owner and end when the token leaves it
+            if self.token_users.contains(_id) {
+                self.token_users.remove(_id);
+                self.env().emit_event(UpdateUser {
+                    _id: _id.clone(),
+                    _user: None,
+                    _expires: 0,
+                });
+            }
+            Ok(())
+        }
+    }
+
+    // Override event emission methods
+    impl psp34::Internal for PeerNftcontract {
+        /// Same as the default implementation, but also records the operators approved for
+        /// each token in `token_approvals`.
+        fn _approve_for(
+            &mut self,
+            to: AccountId,
+            id: Option<Id>,
+            approved: bool,
+        ) -> Result<(), PSP34Error> {
+            let mut caller = Self::env().caller();
+
+            if let Some(id) = &id {
+                let owner = self.owner_of(id.clone()).ok_or(PSP34Error::TokenNotExists)?;
+                if approved && owner == to {
+                    return Err(PSP34Error::SelfApprove);
+                }
+                if owner != caller && !self._allowance(&owner, &caller, &None) {
+                    return Err(PSP34Error::NotApproved);
+                }
+                caller = owner;
+
+                let mut operators = self.token_approvals.get(id).unwrap_or_default();
+                if approved && !operators.contains(&to) {
+                    if operators.len() >= MAX_TOKEN_APPROVALS {
+                        return Err(PSP34Error::Custom(String::from("TooManyApprovals")));
+                    }
+                    operators.push(to);
+                } else if !approved {
+                    operators.retain(|operator| *operator != to);
+                }
+                if operators.is_empty() {
+                    self.token_approvals.remove(id);
+                } else {
+                    self.token_approvals.insert(id, &operators);
+                }
+            }
+
+            if approved {
+                self.psp34
+                    .operator_approvals
+                    .insert(&(&caller, &to, &id.as_ref()), &());
+            } else {
+                self.psp34
+                    .operator_approvals
+                    .remove(&(&caller, &to, &id.as_ref()));
+            }
+            self._emit_approval_event(caller, to, id, approved);
+
+            Ok(())
+        }
+
+        fn _emit_transfer_event(&self, from: Option<AccountId>, to: Option<AccountId>, id: Id) {
+            self.env().emit_event(Transfer { from, to, id });
+        }
+
+        fn _emit_approval_event(
             &self,
             from: AccountId,
             to: AccountId,
@@ -243,6 +1489,27 @@ mod PeerNftcontract {
                 .grant_role(MANAGER, _instance.env().caller())
                 .expect("Should grant MANAGER role");
 
+            // MINTER is administered by its own MINTER_ADMIN role so minting can be
+            // delegated without handing out MANAGER.
+            _instance._set_role_admin(MINTER, MINTER_ADMIN);
+            _instance
+                .grant_role(MINTER_ADMIN, _instance.env().caller())
+                .expect("Should grant MINTER_ADMIN role");
+            _instance
+                .grant_role(MINTER, _instance.env().caller())
+                .expect("Should grant MINTER role");
+
+            // ADMIN reviews which code hashes MANAGER is allowed to upgrade to.
+            _instance
+                .grant_role(ADMIN, _instance.env().caller())
+                .expect("Should grant ADMIN role");
+            _instance
+                .grant_role(PAUSER, _instance.env().caller())
+                .expect("Should grant PAUSER role");
+            _instance
+                .grant_role(RECOVERY, _instance.env().caller())
+                .expect("Should grant RECOVERY role");
+
             // _instance
             //     ._mint_to(_instance.env().caller(), Id::U8(1))
             //     .expect("Can mint");
@@ -250,21 +1517,38 @@ mod PeerNftcontract {
             let collection_id = _instance.collection_id();
             _instance._set_attribute(collection_id.clone(), String::from("name"), collection_name);
             _instance._set_attribute(collection_id, String::from("symbol"), collection_symbol);
+            _instance.upgrade_delay.set(&DEFAULT_UPGRADE_DELAY);
+            _instance.storage_version.set(&STORAGE_VERSION);
             _instance
         }
 
         pub fn _emit_set_token_uri_event(&self, _id: Id, _token_uri: String) {
             self.env().emit_event(SetTokenUri { _id, _token_uri });
         }
-        pub fn _emit_updated_token_uri_event(&self, _id: Id, _token_uri: String) {
-            self.env().emit_event(SetTokenUri { _id, _token_uri });
+        pub fn _emit_updated_token_uri_event(
+            &self,
+            _id: Id,
+            _old_token_uri: Option<String>,
+            _token_uri: String,
+        ) {
+            self.env().emit_event(UpdateTokenUri {
+                _id,
+                _old_token_uri,
+                _token_uri,
+            });
+        }
+        pub fn _emit_remove_token_uri_event(&self, _id: Id) {
+            self.env().emit_event(RemoveTokenUri { _id });
         }
-        pub fn _emit_remove_token_uri_event(&self, _id: Id) {}
 
         /// Only manager
-        // #[openbrush::modifiers(only_role(MANAGER))]
-        fn set_token_uri(&mut self, id: Id, _token_uri: String) -> Result<(), PSP34Error> {
+        // #[openbrush::modifiers(only_manager)]
+        fn set_token_uri(&mut self, id: Id, _token_uri: String) -> Result<(), PeerNftError> {
+            if _token_uri.len() > MAX_TOKEN_URI_LEN {
+                return Err(PeerNftError::UriTooLong);
+            }
             self.token_uris.insert(&id, &_token_uri);
+            self._record_token_uri_history(&id, &_token_uri);
             self._emit_set_token_uri_event(id, _token_uri);
 
             Ok(())
@@ -280,248 +1564,3881 @@ mod PeerNftcontract {
             MANAGER
         }
 
-        /// Only manager
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn remove_token_uri(&mut self, id: Id) -> Result<(), PSP34Error> {
+        #[ink(message)]
+        pub fn minter_role_bytes(&self) -> RoleType {
+            MINTER
+        }
+
+        #[ink(message)]
+        pub fn minter_admin_role_bytes(&self) -> RoleType {
+            MINTER_ADMIN
+        }
+
+        #[ink(message)]
+        pub fn admin_role_bytes(&self) -> RoleType {
+            ADMIN
+        }
+
+        #[ink(message)]
+        pub fn pauser_role_bytes(&self) -> RoleType {
+            PAUSER
+        }
+
+        #[ink(message)]
+        pub fn recovery_role_bytes(&self) -> RoleType {
+            RECOVERY
+        }
+
+        #[ink(message)]
+        pub fn is_paused(&self, operation: PausableOperation) -> bool {
+            self.paused_operations.contains(&operation)
+        }
+
+        /// Only pauser
+        #[ink(message)]
+        #[openbrush::modifiers(only_pauser)]
+        pub fn pause(&mut self, operation: PausableOperation) -> Result<(), PeerNftError> {
+            self._set_paused(operation, true);
+            Ok(())
+        }
+
+        /// Only pauser
+        #[ink(message)]
+        #[openbrush::modifiers(only_pauser)]
+        pub fn unpause(&mut self, operation: PausableOperation) -> Result<(), PeerNftError> {
+            self._set_paused(operation, false);
+            Ok(())
+        }
+
+        /// Only pauser
+        ///
+        /// Emergency stop, pauses every operation.
+        #[ink(message)]
+        #[openbrush::modifiers(only_pauser)]
+        pub fn pause_all(&mut self) -> Result<(), PeerNftError> {
+            for operation in PausableOperation::ALL {
+                self._set_paused(operation, true);
+            }
+            Ok(())
+        }
+
+        /// Only pauser
+        #[ink(message)]
+        #[openbrush::modifiers(only_pauser)]
+        pub fn unpause_all(&mut self) -> Result<(), PeerNftError> {
+            for operation in PausableOperation::ALL {
+                self._set_paused(operation, false);
+            }
+            Ok(())
+        }
+
+        fn _set_paused(&mut self, operation: PausableOperation, paused: bool) {
+            if self.is_paused(operation) == paused {
+                return;
+            }
+            let caller = self.env().caller();
+            if paused {
+                self.paused_operations.insert(&operation, &());
+                self.env().emit_event(Paused {
+                    _operation: operation,
+                    _account: caller,
+                });
+            } else {
+                self.paused_operations.remove(&operation);
+                self.env().emit_event(Unpaused {
+                    _operation: operation,
+                    _account: caller,
+                });
+            }
+        }
+
+        fn _remove_token_uri(&mut self, id: Id) {
             self.token_uris.remove(&id);
             self._emit_remove_token_uri_event(id);
+        }
+
+        /// Only manager
+        #[openbrush::modifiers(only_manager)]
+        pub fn remove_token_uri(&mut self, id: Id) -> Result<(), PeerNftError> {
+            self._remove_token_uri(id);
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Update)
+        )]
+        pub fn update_token_uri(&mut self, id: Id, _token_uri: String) -> Result<(), PeerNftError> {
+            self._check_token_uri_update(&id, &_token_uri)?;
+            self._update_token_uri(id, _token_uri);
+
+            Ok(())
+        }
+
+        fn _check_token_uri_update(&self, id: &Id, _token_uri: &String) -> Result<(), PeerNftError> {
+            self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if _token_uri.len() > MAX_TOKEN_URI_LEN {
+                return Err(PeerNftError::UriTooLong);
+            }
+            Ok(())
+        }
+
+        fn _update_token_uri(&mut self, id: Id, _token_uri: String) {
+            let old_token_uri = self.token_uris.get(&id);
+            self.token_uris.remove(&id);
+            self.token_uris.insert(&id, &_token_uri);
+            self._record_token_uri_history(&id, &_token_uri);
+            self._emit_updated_token_uri_event(id, old_token_uri, _token_uri);
+        }
+
+        pub fn _emit_set_token_location_event(&self, _id: Id, _token_location: String) {
+            self.env().emit_event(SetTokenLocation {
+                _id,
+                _token_location,
+            });
+        }
+        pub fn _emit_updated_token_location_event(
+            &self,
+            _id: Id,
+            _old_token_location: Option<String>,
+            _token_location: String,
+        ) {
+            self.env().emit_event(UpdateTokenLocation {
+                _id,
+                _old_token_location,
+                _token_location,
+            });
+        }
+        pub fn _emit_remove_token_location_event(&self, _id: Id) {
+            self.env().emit_event(RemoveTokenLocation { _id });
+        }
+
+        /// Only manager
+        // #[openbrush::modifiers(only_manager)]
+        fn set_token_location(
+            &mut self,
+            id: Id,
+            _token_location: String,
+        ) -> Result<(), PeerNftError> {
+            if _token_location.len() > MAX_TOKEN_LOCATION_LEN {
+                return Err(PeerNftError::LocationTooLong);
+            }
+            self.token_locations.insert(&id, &_token_location);
+            self._index_token_geohash(&id, &_token_location);
+            self._record_token_location_history(&id, &_token_location, self.env().caller());
+            self._apply_geofences(&id, &_token_location);
+            self._emit_set_token_location_event(id, _token_location);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_token_location(&self, id: Id) -> Option<String> {
+            self.token_locations.get(&id)
+        }
+
+        fn _new_token_update(&self, value: &String, updater: AccountId) -> TokenUpdate {
+            TokenUpdate {
+                value: value.clone(),
+                block_number: self.env().block_number(),
+                timestamp: self.env().block_timestamp(),
+                updater,
+            }
+        }
+
+        fn _record_token_uri_history(&mut self, id: &Id, _token_uri: &String) {
+            let len = self.token_uri_history_len.get(id).unwrap_or(0);
+            let update = self._new_token_update(_token_uri, self.env().caller());
+            self.token_uri_history.insert(&(id.clone(), len), &update);
+            self.token_uri_history_len.insert(id, &(len + 1));
+        }
+
+        fn _record_token_location_history(
+            &mut self,
+            id: &Id,
+            _token_location: &String,
+            updater: AccountId,
+        ) {
+            let len = self.token_location_history_len.get(id).unwrap_or(0);
+            let update = self._new_token_update(_token_location, updater);
+            self.token_location_history.insert(&(id.clone(), len), &update);
+            self.token_location_history_len.insert(id, &(len + 1));
+        }
+
+        #[ink(message)]
+        pub fn token_uri_history_len(&self, id: Id) -> u32 {
+            self.token_uri_history_len.get(&id).unwrap_or(0)
+        }
+
+        /// Returns up to `limit` entries of the `token_uri` history of `id`, oldest first,
+        /// starting at `offset`.
+        ///
+        /// `limit` is capped at `MAX_PAGE_SIZE`.
+        #[ink(message)]
+        pub fn token_uri_history(&self, id: Id, offset: u32, limit: u32) -> Vec<TokenUpdate> {
+            let end = self
+                .token_uri_history_len(id.clone())
+                .min(offset.saturating_add(limit.min(MAX_PAGE_SIZE)));
+
+            let mut history: Vec<TokenUpdate> = Vec::new();
+            for index in offset..end {
+                if let Some(update) = self.token_uri_history.get(&(id.clone(), index)) {
+                    history.push(update);
+                }
+            }
+            history
+        }
+
+        #[ink(message)]
+        pub fn token_location_history_len(&self, id: Id) -> u32 {
+            self.token_location_history_len.get(&id).unwrap_or(0)
+        }
+
+        /// Returns up to `limit` entries of the `token_location` history of `id`, oldest first,
+        /// starting at `offset`.
+        ///
+        /// `limit` is capped at `MAX_PAGE_SIZE`.
+        #[ink(message)]
+        pub fn token_location_history(&self, id: Id, offset: u32, limit: u32) -> Vec<TokenUpdate> {
+            let end = self
+                .token_location_history_len(id.clone())
+                .min(offset.saturating_add(limit.min(MAX_PAGE_SIZE)));
+
+            let mut history: Vec<TokenUpdate> = Vec::new();
+            for index in offset..end {
+                if let Some(update) = self.token_location_history.get(&(id.clone(), index)) {
+                    history.push(update);
+                }
+            }
+            history
+        }
+
+        fn _remove_token_location(&mut self, id: Id) {
+            self.token_locations.remove(&id);
+            self.token_geo_locations.remove(&id);
+            self.token_location_attestations.remove(&id);
+            self._unindex_token_geohash(&id);
+            self._exit_geofence(&id);
+            self._emit_remove_token_location_event(id);
+        }
+
+        /// Only manager
+        #[openbrush::modifiers(only_manager)]
+        pub fn remove_token_location(&mut self, id: Id) -> Result<(), PeerNftError> {
+            self._remove_token_location(id);
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Update)
+        )]
+        pub fn update_token_location(
+            &mut self,
+            id: Id,
+            _token_location: String,
+        ) -> Result<(), PeerNftError> {
+            self._check_token_location_update(&id, &_token_location)?;
+            self._update_token_location(id, _token_location, self.env().caller());
+
+            Ok(())
+        }
+
+        fn _check_token_location_update(
+            &self,
+            id: &Id,
+            _token_location: &String,
+        ) -> Result<(), PeerNftError> {
+            self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if _token_location.len() > MAX_TOKEN_LOCATION_LEN {
+                return Err(PeerNftError::LocationTooLong);
+            }
+            Ok(())
+        }
+
+        /// Replaces the location of `id`, `updater` is recorded in the location history.
+        fn _update_token_location(&mut self, id: Id, _token_location: String, updater: AccountId) {
+            let old_token_location = self.token_locations.get(&id);
+            self.token_locations.remove(&id);
+            self.token_locations.insert(&id, &_token_location);
+            // A free-form location supersedes any structured or attested one
+            self.token_geo_locations.remove(&id);
+            self.token_location_attestations.remove(&id);
+            self._index_token_geohash(&id, &_token_location);
+            self._record_token_location_history(&id, &_token_location, updater);
+            self._apply_geofences(&id, &_token_location);
+            self._emit_updated_token_location_event(id, old_token_location, _token_location);
+        }
+
+        /// Only manager
+        ///
+        /// Sets the structured location of `id` and stores its string form in `token_locations`.
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Update)
+        )]
+        pub fn set_token_geo_location(
+            &mut self,
+            id: Id,
+            location: Location,
+        ) -> Result<(), PeerNftError> {
+            if !location.is_valid() {
+                return Err(PeerNftError::InvalidLocation);
+            }
+            let _token_location = location.to_location_string();
+            self._check_token_location_update(&id, &_token_location)?;
+            self._update_token_location(id.clone(), _token_location, self.env().caller());
+            self.token_geo_locations.insert(&id, &location);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn get_token_geo_location(&self, id: Id) -> Option<Location> {
+            self.token_geo_locations.get(&id)
+        }
+
+        #[ink(message)]
+        pub fn is_location_oracle(&self, oracle: [u8; 33]) -> bool {
+            self.location_oracles.contains(&oracle)
+        }
+
+        /// Returns the attestation of the current location of `id`, `None` if the location was
+        /// set without one.
+        #[ink(message)]
+        pub fn token_location_attestation(&self, id: Id) -> Option<LocationAttestation> {
+            self.token_location_attestations.get(&id)
+        }
+
+        /// Only manager
+        ///
+        /// Allows or disallows the compressed ECDSA public key `oracle` to attest token locations.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_location_oracle(
+            &mut self,
+            oracle: [u8; 33],
+            allowed: bool,
+        ) -> Result<(), PeerNftError> {
+            if allowed {
+                self.location_oracles.insert(&oracle, &());
+            } else {
+                self.location_oracles.remove(&oracle);
+            }
+            self.env().emit_event(LocationOracleSet {
+                _oracle: oracle,
+                _allowed: allowed,
+            });
+            Ok(())
+        }
+
+        /// Sets the location of `id` from an oracle attestation. Anybody can relay it, the
+        /// oracle signs with ECDSA secp256k1 the Blake2x256 hash of the SCALE encoded
+        /// `(contract AccountId, id, location, timestamp)`.
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Update))]
+        pub fn submit_location_attestation(
+            &mut self,
+            id: Id,
+            location: String,
+            timestamp: Timestamp,
+            signature: [u8; 65],
+        ) -> Result<(), PeerNftError> {
+            let now = self.env().block_timestamp();
+            let last = self.last_attestation_timestamps.get(&id);
+            if timestamp > now
+                || now - timestamp > MAX_ATTESTATION_AGE
+                || last.map_or(false, |last| timestamp <= last)
+            {
+                return Err(PeerNftError::StaleAttestation);
+            }
+            self._check_token_location_update(&id, &location)?;
+
+            let mut message_hash = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(
+                &(self.env().account_id(), &id, &location, timestamp),
+                &mut message_hash,
+            );
+            let mut oracle = [0u8; 33];
+            ink::env::ecdsa_recover(&signature, &message_hash, &mut oracle)
+                .map_err(|_| PeerNftError::InvalidSignature)?;
+            if !self.location_oracles.contains(&oracle) {
+                return Err(PeerNftError::UnknownOracle);
+            }
+
+            self._update_token_location(id.clone(), location, Self::_oracle_account_id(&oracle));
+            self.token_location_attestations
+                .insert(&id, &LocationAttestation { oracle, timestamp });
+            self.last_attestation_timestamps.insert(&id, &timestamp);
+            self.env().emit_event(LocationAttested {
+                _id: id,
+                _oracle: oracle,
+                _timestamp: timestamp,
+            });
+            Ok(())
+        }
+
+        /// Returns the account of the compressed ECDSA public key `oracle`, the Blake2x256 hash
+        /// of the key as the chain derives ECDSA accounts.
+        fn _oracle_account_id(oracle: &[u8; 33]) -> AccountId {
+            let mut account_id = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(oracle, &mut account_id);
+            AccountId::from(account_id)
+        }
+
+        #[ink(message)]
+        pub fn geofence(&self, fence_id: u32) -> Option<Geofence> {
+            self.geofences.get(&fence_id)
+        }
+
+        /// Returns the ids of the registered geofences in the order they are evaluated.
+        #[ink(message)]
+        pub fn geofence_ids(&self) -> Vec<u32> {
+            self.geofence_areas
+                .get()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(fence_id, _)| fence_id)
+                .collect()
+        }
+
+        /// Returns the geofence the current location of `id` is in.
+        #[ink(message)]
+        pub fn token_geofence(&self, id: Id) -> Option<u32> {
+            self.token_geofences.get(&id)
+        }
+
+        /// Only manager
+        ///
+        /// Registers a geofence and returns its id. Tokens whose location later changes to a
+        /// point inside `area` get `token_uri` and `attributes` applied. Tokens already inside
+        /// are only picked up on their next location change.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn add_geofence(
+            &mut self,
+            area: GeofenceArea,
+            token_uri: String,
+            attributes: Vec<(String, String)>,
+        ) -> Result<u32, PeerNftError> {
+            let attribute_too_long = |(key, value): &(String, String)| {
+                key.len() > MAX_GEOFENCE_ATTRIBUTE_LEN || value.len() > MAX_GEOFENCE_ATTRIBUTE_LEN
+            };
+            if !area.is_valid()
+                || attributes.len() > MAX_GEOFENCE_ATTRIBUTES
+                || attributes.iter().any(attribute_too_long)
+            {
+                return Err(PeerNftError::InvalidGeofence);
+            }
+            if token_uri.len() > MAX_TOKEN_URI_LEN {
+                return Err(PeerNftError::UriTooLong);
+            }
+            let mut geofence_areas = self.geofence_areas.get().unwrap_or_default();
+            if geofence_areas.len() >= MAX_GEOFENCES {
+                return Err(PeerNftError::TooManyGeofences);
+            }
+
+            let fence_id = self.next_geofence_id.get().unwrap_or_default();
+            let geofence = Geofence {
+                area,
+                token_uri,
+                attributes,
+            };
+            self.geofences.insert(&fence_id, &geofence);
+            geofence_areas.push((fence_id, geofence.area.clone()));
+            self.geofence_areas.set(&geofence_areas);
+            self.next_geofence_id.set(&(fence_id + 1));
+            self.env().emit_event(GeofenceAdded {
+                _fence_id: fence_id,
+                _geofence: geofence,
+            });
+            Ok(fence_id)
+        }
+
+        /// Only manager
+        ///
+        /// Tokens inside the removed geofence keep its uri and attributes, they exit it on
+        /// their next location change.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn remove_geofence(&mut self, fence_id: u32) -> Result<(), PeerNftError> {
+            let mut geofence_areas = self.geofence_areas.get().unwrap_or_default();
+            let index = geofence_areas
+                .iter()
+                .position(|(id, _)| *id == fence_id)
+                .ok_or(PeerNftError::GeofenceNotFound)?;
+            geofence_areas.remove(index);
+            self.geofence_areas.set(&geofence_areas);
+            self.geofences.remove(&fence_id);
+            self.env().emit_event(GeofenceRemoved {
+                _fence_id: fence_id,
+            });
+            Ok(())
+        }
+
+        /// Moves `id` to the first registered geofence containing `_token_location`.
+        ///
+        /// Entering a geofence applies its token uri and attributes, leaving one keeps them.
+        /// Locations that aren't in `"lat,lng"` form are outside every geofence. Only the areas
+        /// are read to find the geofence, its uri and attributes are loaded on entering it.
+        fn _apply_geofences(&mut self, id: &Id, _token_location: &String) {
+            let entered = core::str::from_utf8(_token_location)
+                .ok()
+                .and_then(Location::parse_coordinates)
+                .and_then(|(lat_e7, lng_e7)| {
+                    let geofence_areas = self.geofence_areas.get().unwrap_or_default();
+                    geofence_areas
+                        .into_iter()
+                        .find(|(_, area)| area.contains(lat_e7, lng_e7))
+                        .map(|(fence_id, _)| fence_id)
+                });
+            if self.token_geofences.get(id) == entered {
+                return;
+            }
+
+            self._exit_geofence(id);
+            let Some(fence_id) = entered else {
+                return;
+            };
+            let Some(fence) = self.geofences.get(&fence_id) else {
+                return;
+            };
+            self.token_geofences.insert(id, &fence_id);
+            self._update_token_uri(id.clone(), fence.token_uri);
+            for (key, value) in fence.attributes {
+                self._set_attribute(id.clone(), key, value);
+            }
+            self.env().emit_event(GeofenceEntered {
+                _id: id.clone(),
+                _fence_id: fence_id,
+            });
+        }
+
+        fn _exit_geofence(&mut self, id: &Id) {
+            let Some(fence_id) = self.token_geofences.get(id) else {
+                return;
+            };
+            self.token_geofences.remove(id);
+            self.env().emit_event(GeofenceExited {
+                _id: id.clone(),
+                _fence_id: fence_id,
+            });
+        }
+
+        /// Adds `id` to the geohash cells of `_token_location`, replacing its previous cells.
+        ///
+        /// Locations that aren't in `"lat,lng"` form are not indexed.
+        fn _index_token_geohash(&mut self, id: &Id, _token_location: &String) {
+            self._unindex_token_geohash(id);
+            let Some((lat_e7, lng_e7)) = core::str::from_utf8(_token_location)
+                .ok()
+                .and_then(Location::parse_coordinates)
+            else {
+                return;
+            };
+            let geohash = Location::geohash(lat_e7, lng_e7, GEOHASH_PRECISION);
+
+            for len in 1..=geohash.len() {
+                let prefix = String::from(&geohash[..len]);
+                let count = self.geohash_token_count.get(&prefix).unwrap_or(0);
+                self.geohash_tokens.insert(&(prefix.clone(), count), id);
+                self.geohash_token_index.insert(&(prefix.clone(), id.clone()), &count);
+                self.geohash_token_count.insert(&prefix, &(count + 1));
+            }
+            self.token_geohashes.insert(id, &geohash);
+        }
+
+        fn _unindex_token_geohash(&mut self, id: &Id) {
+            let Some(geohash) = self.token_geohashes.get(id) else {
+                return;
+            };
+
+            for len in 1..=geohash.len() {
+                let prefix = String::from(&geohash[..len]);
+                let count = self.geohash_token_count.get(&prefix).unwrap_or(0);
+                let Some(index) = self.geohash_token_index.get(&(prefix.clone(), id.clone())) else {
+                    continue;
+                };
+                let last = count - 1;
+
+                // Swap the last token of the cell into the removed slot
+                if index != last {
+                    if let Some(last_id) = self.geohash_tokens.get(&(prefix.clone(), last)) {
+                        self.geohash_tokens.insert(&(prefix.clone(), index), &last_id);
+                        self.geohash_token_index.insert(&(prefix.clone(), last_id), &index);
+                    }
+                }
+                self.geohash_tokens.remove(&(prefix.clone(), last));
+                self.geohash_token_index.remove(&(prefix.clone(), id.clone()));
+                self.geohash_token_count.insert(&prefix, &last);
+            }
+            self.token_geohashes.remove(id);
+        }
+
+        #[ink(message)]
+        pub fn get_token_geohash(&self, id: Id) -> Option<String> {
+            self.token_geohashes.get(&id)
+        }
+
+        #[ink(message)]
+        pub fn geohash_token_count(&self, prefix: String) -> u32 {
+            self.geohash_token_count.get(&prefix).unwrap_or(0)
+        }
+
+        /// Returns up to `limit` tokens located in the geohash cell `prefix`, starting at `offset`.
+        ///
+        /// Cells are indexed up to `GEOHASH_PRECISION` characters. `limit` is capped at
+        /// `MAX_PAGE_SIZE`.
+        #[ink(message)]
+        pub fn tokens_in_geohash(&self, prefix: String, offset: u32, limit: u32) -> Vec<Id> {
+            let end = self
+                .geohash_token_count(prefix.clone())
+                .min(offset.saturating_add(limit.min(MAX_PAGE_SIZE)));
+
+            let mut ids_vec: Vec<Id> = Vec::new();
+            for index in offset..end {
+                if let Some(id) = self.geohash_tokens.get(&(prefix.clone(), index)) {
+                    ids_vec.push(id);
+                }
+            }
+            ids_vec
+        }
+
+        /// Only manager
+        ///
+        /// Updates the uri of every `(id, token_uri)` item. The whole batch is validated up front,
+        /// so either every token is updated or none is.
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Update)
+        )]
+        pub fn batch_update_token_uri(
+            &mut self,
+            updates: Vec<(Id, String)>,
+        ) -> Result<(), PeerNftError> {
+            Self::_check_update_batch_size(updates.len())?;
+            for (id, _token_uri) in updates.iter() {
+                self._check_token_uri_update(id, _token_uri)?;
+            }
+
+            for (id, _token_uri) in updates {
+                self._update_token_uri(id, _token_uri);
+            }
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// Updates the location of every `(id, token_location)` item. The whole batch is validated
+        /// up front, so either every token is updated or none is.
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Update)
+        )]
+        pub fn batch_update_token_location(
+            &mut self,
+            updates: Vec<(Id, String)>,
+        ) -> Result<(), PeerNftError> {
+            Self::_check_update_batch_size(updates.len())?;
+            for (id, _token_location) in updates.iter() {
+                self._check_token_location_update(id, _token_location)?;
+            }
+
+            for (id, _token_location) in updates {
+                self._update_token_location(id, _token_location, self.env().caller());
+            }
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// Updates both the location and the uri of every `(id, token_location, token_uri)` item.
+        /// The whole batch is validated up front, so either every token is updated or none is.
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Update)
+        )]
+        pub fn batch_update_token_data(
+            &mut self,
+            updates: Vec<(Id, String, String)>,
+        ) -> Result<(), PeerNftError> {
+            Self::_check_update_batch_size(updates.len())?;
+            for (id, _token_location, _token_uri) in updates.iter() {
+                self._check_token_location_update(id, _token_location)?;
+                self._check_token_uri_update(id, _token_uri)?;
+            }
+
+            for (id, _token_location, _token_uri) in updates {
+                self._update_token_location(id.clone(), _token_location, self.env().caller());
+                self._update_token_uri(id, _token_uri);
+            }
+            Ok(())
+        }
+
+        fn _check_update_batch_size(len: usize) -> Result<(), PeerNftError> {
+            if len == 0 || len > MAX_UPDATE_BATCH_SIZE as usize {
+                return Err(PeerNftError::InvalidBatchSize);
+            }
+            Ok(())
+        }
+
+        /// Internal mint shared by `mint` and `manager_mint`
+        fn _mint_token(
+            &mut self,
+            account: AccountId,
+            _token_location: String,
+            _token_uri: String,
+        ) -> Result<(), PeerNftError> {
+            let transferable = !self.collection_non_transferable.get().unwrap_or_default();
+            self._mint_token_with_transferable(account, _token_location, _token_uri, transferable)
+        }
+
+        fn _mint_token_with_transferable(
+            &mut self,
+            account: AccountId,
+            _token_location: String,
+            _token_uri: String,
+            transferable: bool,
+        ) -> Result<(), PeerNftError> {
+            let max_supply = self.max_supply.get().unwrap_or_default();
+            if max_supply != 0 && self.next_id >= max_supply {
+                return Err(PeerNftError::SupplyExhausted);
+            }
+            if _token_uri.len() > MAX_TOKEN_URI_LEN {
+                return Err(PeerNftError::UriTooLong);
+            }
+            if _token_location.len() > MAX_TOKEN_LOCATION_LEN {
+                return Err(PeerNftError::LocationTooLong);
+            }
+            let id = Id::U32(self.next_id);
+            self._mint_to(account, id.clone())?;
+            self.set_token_uri(id.clone(), _token_uri)?;
+            self.set_token_location(id.clone(), _token_location)?;
+            if !transferable {
+                self.non_transferable_tokens.insert(&id, &());
+                self.env().emit_event(TransferableSet {
+                    _id: Some(id),
+                    _transferable: false,
+                });
+            }
+            self.next_id += 1;
+            Ok(())
+        }
+
+        /// Mints the next id to `account` without uri or location, so nothing is recorded in their
+        /// histories and no SetTokenUri or SetTokenLocation event is emitted.
+        fn _mint_blank_token(&mut self, account: AccountId) -> Result<(), PeerNftError> {
+            let max_supply = self.max_supply.get().unwrap_or_default();
+            if max_supply != 0 && self.next_id >= max_supply {
+                return Err(PeerNftError::SupplyExhausted);
+            }
+            let id = Id::U32(self.next_id);
+            self._mint_to(account, id.clone())?;
+            if self.collection_non_transferable.get().unwrap_or_default() {
+                self.non_transferable_tokens.insert(&id, &());
+                self.env().emit_event(TransferableSet {
+                    _id: Some(id),
+                    _transferable: false,
+                });
+            }
+            self.next_id += 1;
+            Ok(())
+        }
+
+        /// Minters mint for free at any time. Everybody else can mint only while the
+        /// public sale is open, paying exactly `mint_price` and staying within
+        /// `max_mints_per_account`.
+        #[ink(message, payable)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Mint))]
+        pub fn mint(
+            &mut self,
+            account: AccountId,
+            _token_location: String,
+            _token_uri: String,
+        ) -> Result<(), PeerNftError> {
+            let caller = self.env().caller();
+            let value = self.env().transferred_value();
+
+            if self.has_role(MINTER, caller) {
+                self._mint_token(account, _token_location, _token_uri)?;
+                self._add_sale_proceeds(value);
+                return Ok(());
+            }
+
+            if self.sale_phase.get().unwrap_or_default() != SalePhase::Public {
+                return Err(PeerNftError::NotMinter);
+            }
+            if value != self.mint_price.get().unwrap_or_default() {
+                return Err(PeerNftError::IncorrectPayment);
+            }
+            let minted = self.public_mints.get(&caller).unwrap_or(0);
+            let max_mints = self.max_mints_per_account.get().unwrap_or_default();
+            if max_mints != 0 && minted >= max_mints {
+                return Err(PeerNftError::MintLimitReached);
+            }
+
+            self._mint_token(account, _token_location, _token_uri)?;
+            self.public_mints.insert(&caller, &(minted + 1));
+            self._add_sale_proceeds(value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_voucher_signer(&self, signer: [u8; 33]) -> bool {
+            self.voucher_signers.contains(&signer)
+        }
+
+        #[ink(message)]
+        pub fn is_voucher_nonce_used(&self, nonce: u64) -> bool {
+            self.used_voucher_nonces.contains(&nonce)
+        }
+
+        /// Only manager
+        ///
+        /// Allows or disallows the compressed ECDSA public key `signer` to sign mint vouchers.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_voucher_signer(
+            &mut self,
+            signer: [u8; 33],
+            allowed: bool,
+        ) -> Result<(), PeerNftError> {
+            if allowed {
+                self.voucher_signers.insert(&signer, &());
+            } else {
+                self.voucher_signers.remove(&signer);
+            }
+            self.env().emit_event(VoucherSignerSet {
+                _signer: signer,
+                _allowed: allowed,
+            });
+            Ok(())
+        }
+
+        /// Mints the token described by `voucher` to its recipient. Anybody can redeem a voucher
+        /// by transferring exactly its price, which is added to `sale_proceeds`.
+        #[ink(message, payable)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Mint))]
+        pub fn redeem_voucher(
+            &mut self,
+            voucher: MintVoucher,
+            signature: [u8; 65],
+        ) -> Result<(), PeerNftError> {
+            let value = self.env().transferred_value();
+            if self.env().block_timestamp() >= voucher.expiry {
+                return Err(PeerNftError::VoucherExpired);
+            }
+            if value != voucher.price {
+                return Err(PeerNftError::IncorrectPayment);
+            }
+            if self.used_voucher_nonces.contains(&voucher.nonce) {
+                return Err(PeerNftError::VoucherAlreadyUsed);
+            }
+            let signer = self._voucher_signer(&voucher, &signature)?;
+            if !self.voucher_signers.contains(&signer) {
+                return Err(PeerNftError::UnknownSigner);
+            }
+
+            let id = Id::U32(self.next_id);
+            self.used_voucher_nonces.insert(&voucher.nonce, &());
+            self._mint_token(voucher.recipient, voucher.token_location, voucher.token_uri)?;
+            self._add_sale_proceeds(value);
+            self.env().emit_event(VoucherRedeemed {
+                _id: id,
+                _recipient: voucher.recipient,
+                _nonce: voucher.nonce,
+            });
+            Ok(())
+        }
+
+        /// Recovers the compressed public key that signed `voucher` for this contract.
+        fn _voucher_signer(
+            &self,
+            voucher: &MintVoucher,
+            signature: &[u8; 65],
+        ) -> Result<[u8; 33], PeerNftError> {
+            let mut message_hash = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(
+                &(self.env().account_id(), voucher),
+                &mut message_hash,
+            );
+
+            let mut signer = [0u8; 33];
+            ink::env::ecdsa_recover(signature, &message_hash, &mut signer)
+                .map_err(|_| PeerNftError::InvalidSignature)?;
+            Ok(signer)
+        }
+
+        /// Mints `quantity` tokens to the caller during the presale, paying `mint_price` for each.
+        /// `proof` proves the caller is in the allowlist, and each account can mint at most
+        /// `presale_quota` tokens. Token uris and locations are left unset for the manager to set.
+        #[ink(message, payable)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Mint))]
+        pub fn presale_mint(
+            &mut self,
+            proof: Vec<[u8; 32]>,
+            quantity: u32,
+        ) -> Result<(), PeerNftError> {
+            let caller = self.env().caller();
+            if self.sale_phase.get().unwrap_or_default() != SalePhase::Presale {
+                return Err(PeerNftError::PresaleClosed);
+            }
+            if quantity == 0 || quantity > MAX_BATCH_SIZE {
+                return Err(PeerNftError::InvalidBatchSize);
+            }
+            let root = self
+                .presale_merkle_root
+                .get()
+                .unwrap_or_default()
+                .ok_or(PeerNftError::NotAllowlisted)?;
+            if !verify_merkle_proof(&proof, root, presale_leaf(&caller)) {
+                return Err(PeerNftError::NotAllowlisted);
+            }
+            let minted = self.presale_mints.get(&caller).unwrap_or(0);
+            if minted.saturating_add(quantity) > self.presale_quota.get().unwrap_or_default() {
+                return Err(PeerNftError::MintLimitReached);
+            }
+            let price = self.mint_price.get().unwrap_or_default();
+            if self.env().transferred_value() != price.saturating_mul(quantity as Balance) {
+                return Err(PeerNftError::IncorrectPayment);
+            }
+            let max_supply = self.max_supply.get().unwrap_or_default();
+            if max_supply != 0 && self.next_id.saturating_add(quantity) > max_supply {
+                return Err(PeerNftError::SupplyExhausted);
+            }
+
+            for _ in 0..quantity {
+                self._mint_blank_token(caller)?;
+            }
+            self.presale_mints.insert(&caller, &(minted + quantity));
+            self._add_sale_proceeds(self.env().transferred_value());
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Mint)
+        )]
+        pub fn manager_mint(
+            &mut self,
+            account: AccountId,
+            _token_location: String,
+            _token_uri: String,
+        ) -> Result<(), PeerNftError> {
+            self._mint_token(account, _token_location, _token_uri)
+        }
+
+        /// Only manager
+        ///
+        /// Mints a token that is transferable or not regardless of the collection default.
+        /// Non-transferable tokens can never move, but they can be burned.
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Mint)
+        )]
+        pub fn manager_mint_with_transferable(
+            &mut self,
+            account: AccountId,
+            _token_location: String,
+            _token_uri: String,
+            transferable: bool,
+        ) -> Result<(), PeerNftError> {
+            self._mint_token_with_transferable(account, _token_location, _token_uri, transferable)
+        }
+
+        #[ink(message)]
+        pub fn is_transferable(&self, id: Id) -> bool {
+            !self.non_transferable_tokens.contains(&id)
+        }
+
+        #[ink(message)]
+        pub fn collection_transferable(&self) -> bool {
+            !self.collection_non_transferable.get().unwrap_or_default()
+        }
+
+        /// Only manager
+        ///
+        /// Sets whether tokens minted from now on are transferable. Already minted tokens keep
+        /// the flag they were minted with.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_collection_transferable(
+            &mut self,
+            transferable: bool,
+        ) -> Result<(), PeerNftError> {
+            self.collection_non_transferable.set(&!transferable);
+            self.env().emit_event(TransferableSet {
+                _id: None,
+                _transferable: transferable,
+            });
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// Mints one token per `(account, token_location, token_uri)` item with consecutive ids
+        /// starting at `next_id`. The whole batch is validated up front, so either every token
+        /// is minted or none is.
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Mint)
+        )]
+        pub fn manager_batch_mint(
+            &mut self,
+            tokens: Vec<(AccountId, String, String)>,
+        ) -> Result<(), PeerNftError> {
+            let count = tokens.len() as u32;
+            if count == 0 || count > MAX_BATCH_SIZE {
+                return Err(PeerNftError::InvalidBatchSize);
+            }
+            let max_supply = self.max_supply.get().unwrap_or_default();
+            if max_supply != 0 && self.next_id.saturating_add(count) > max_supply {
+                return Err(PeerNftError::SupplyExhausted);
+            }
+            for (_, _token_location, _token_uri) in tokens.iter() {
+                if _token_uri.len() > MAX_TOKEN_URI_LEN {
+                    return Err(PeerNftError::UriTooLong);
+                }
+                if _token_location.len() > MAX_TOKEN_LOCATION_LEN {
+                    return Err(PeerNftError::LocationTooLong);
+                }
+            }
+
+            for (account, _token_location, _token_uri) in tokens {
+                self._mint_token(account, _token_location, _token_uri)?;
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn public_sale_open(&self) -> bool {
+            self.sale_phase.get().unwrap_or_default() == SalePhase::Public
+        }
+
+        #[ink(message)]
+        pub fn sale_phase(&self) -> SalePhase {
+            self.sale_phase.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn presale_merkle_root(&self) -> Option<[u8; 32]> {
+            self.presale_merkle_root.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn presale_quota(&self) -> u32 {
+            self.presale_quota.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn presale_mints_of(&self, account: AccountId) -> u32 {
+            self.presale_mints.get(&account).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn mint_price(&self) -> Balance {
+            self.mint_price.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn max_supply(&self) -> u32 {
+            self.max_supply.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn max_mints_per_account(&self) -> u32 {
+            self.max_mints_per_account.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn public_mints_of(&self, account: AccountId) -> u32 {
+            self.public_mints.get(&account).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn sale_proceeds(&self) -> Balance {
+            self.sale_proceeds.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn treasury(&self) -> Option<AccountId> {
+            self.treasury.get().unwrap_or_default()
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_sale_phase(&mut self, phase: SalePhase) -> Result<(), PeerNftError> {
+            self.sale_phase.set(&phase);
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// Sets the presale allowlist, see `presale_leaf` for how leaves are built.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_presale_merkle_root(
+            &mut self,
+            root: Option<[u8; 32]>,
+        ) -> Result<(), PeerNftError> {
+            self.presale_merkle_root.set(&root);
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_presale_quota(&mut self, quota: u32) -> Result<(), PeerNftError> {
+            self.presale_quota.set(&quota);
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_mint_price(&mut self, price: Balance) -> Result<(), PeerNftError> {
+            self.mint_price.set(&price);
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// `0` removes the cap. The cap can't be set below the number of tokens already minted.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_max_supply(&mut self, max_supply: u32) -> Result<(), PeerNftError> {
+            if max_supply != 0 && max_supply < self.next_id {
+                return Err(PeerNftError::SupplyBelowMinted);
+            }
+            self.max_supply.set(&max_supply);
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_max_mints_per_account(&mut self, max_mints: u32) -> Result<(), PeerNftError> {
+            self.max_mints_per_account.set(&max_mints);
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_treasury(&mut self, treasury: AccountId) -> Result<(), PeerNftError> {
+            self.treasury.set(&Some(treasury));
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// Sends all accumulated sale proceeds to the treasury account.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn withdraw_proceeds(&mut self) -> Result<Balance, PeerNftError> {
+            let treasury = self
+                .treasury
+                .get()
+                .unwrap_or_default()
+                .ok_or(PeerNftError::TreasuryNotSet)?;
+            let amount = self.sale_proceeds.get().unwrap_or_default();
+
+            self.sale_proceeds.set(&0);
+            if self.env().transfer(treasury, amount).is_err() {
+                return Err(PeerNftError::TransferFailed);
+            }
+            Ok(amount)
+        }
+
+        fn _add_sale_proceeds(&mut self, amount: Balance) {
+            let sale_proceeds = self.sale_proceeds.get().unwrap_or_default();
+            self.sale_proceeds.set(&(sale_proceeds + amount));
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_manager,
+            when_migrated,
+            when_not_paused(PausableOperation::Burn)
+        )]
+        pub fn manager_burn(
+            &mut self,
+            account: AccountId,
+            id: Id,
+            _token_location: String,
+            _token_uri: String,
+        ) -> Result<(), PeerNftError> {
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if self.auctions.contains(&id) {
+                return Err(PeerNftError::TokenInAuction);
+            }
+            if account != owner {
+                return Err(PeerNftError::NotTokenOwner);
+            }
+            self._burn_from(account, id.clone())?;
+            self._clear_burned_token(id);
+            Ok(())
+        }
+
+        /// Removes everything stored for a token that was just burned
+        fn _clear_burned_token(&mut self, id: Id) {
+            self._remove_token_uri(id.clone());
+            self._remove_token_location(id.clone());
+            self.token_royalties.remove(&id);
+            self.non_transferable_tokens.remove(&id);
+        }
+
+        /// Only recovery
+        ///
+        /// Moves `id` to `to` without the owner's consent, e.g. to recover a token from a lost or
+        /// compromised account. `reason` is an off-chain defined, non-zero code recorded in the
+        /// `ForcedTransfer` event.
+        #[ink(message)]
+        #[openbrush::modifiers(
+            only_recovery,
+            when_migrated,
+            when_not_paused(PausableOperation::Transfer)
+        )]
+        pub fn recovery_transfer(
+            &mut self,
+            to: AccountId,
+            id: Id,
+            reason: u32,
+        ) -> Result<(), PeerNftError> {
+            if reason == 0 {
+                return Err(PeerNftError::MissingReason);
+            }
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if self.auctions.contains(&id) {
+                return Err(PeerNftError::TokenInAuction);
+            }
+            if !self.is_transferable(id.clone()) {
+                return Err(PeerNftError::NotTransferable);
+            }
+
+            self._transfer(to, id.clone())?;
+            self.env().emit_event(ForcedTransfer {
+                _id: id,
+                _from: owner,
+                _to: to,
+                _operator: self.env().caller(),
+                _reason: reason,
+            });
+            Ok(())
+        }
+
+        fn _clear_token_approvals(&mut self, owner: &AccountId, id: &Id) {
+            for operator in self.token_approvals.get(id).unwrap_or_default() {
+                self.psp34
+                    .operator_approvals
+                    .remove(&(owner, &operator, &Some(id)));
+            }
+            self.token_approvals.remove(id);
+        }
+
+        /// Internal transfer function, approvals on the token are cleared by `_after_token_transfer`
+        fn _transfer(&mut self, to: AccountId, id: Id) -> Result<(), PeerNftError> {
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+
+            self._before_token_transfer(Some(&owner), Some(&to), &id)?;
+
+            self.psp34.balances.decrease_balance(&owner, &id, false);
+            self.psp34.token_owner.remove(&id);
+
+            self.psp34.balances.increase_balance(&to, &id, false);
+            self.psp34.token_owner.insert(&id, &to);
+            self._after_token_transfer(Some(&owner), Some(&to), &id)?;
+            self._emit_transfer_event(Some(owner), Some(to), id);
+
+            Ok(())
+        }
+
+        /// Storage written before `upgrade_delay` existed reads as `DEFAULT_UPGRADE_DELAY`, so
+        /// upgrades stay timelocked until `migrate` stores it.
+        #[ink(message)]
+        pub fn upgrade_delay(&self) -> Timestamp {
+            self.upgrade_delay.get().unwrap_or(DEFAULT_UPGRADE_DELAY)
+        }
+
+        #[ink(message)]
+        pub fn pending_upgrade(&self) -> Option<PendingUpgrade> {
+            self.pending_upgrade.get().unwrap_or_default()
+        }
+
+        /// Only manager
+        ///
+        /// Only applies to upgrades proposed afterwards.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager)]
+        pub fn set_upgrade_delay(&mut self, delay: Timestamp) -> Result<(), PeerNftError> {
+            if delay < MIN_UPGRADE_DELAY {
+                return Err(PeerNftError::UpgradeDelayTooShort);
+            }
+            let previous = self.upgrade_delay();
+            self.upgrade_delay.set(&delay);
+            self.env().emit_event(UpgradeDelayChanged {
+                _previous: previous,
+                _new: delay,
+            });
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// Proposes to modify the code which is used to execute calls to this contract address
+        /// (`AccountId`). The upgrade can be executed once `upgrade_delay` has passed, giving token
+        /// holders time to react.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager)]
+        pub fn propose_upgrade(&mut self, code_hash: [u8; 32]) -> Result<(), PeerNftError> {
+            if !self.allowed_code_hashes.contains(&code_hash) {
+                return Err(PeerNftError::CodeHashNotAllowed);
+            }
+            if self.pending_upgrade.get().unwrap_or_default().is_some() {
+                return Err(PeerNftError::UpgradeAlreadyPending);
+            }
+            let executable_at = self.env().block_timestamp().saturating_add(self.upgrade_delay());
+            self.pending_upgrade.set(&Some(PendingUpgrade {
+                code_hash,
+                executable_at,
+            }));
+            self.env().emit_event(UpgradeProposed {
+                _code_hash: code_hash,
+                _executable_at: executable_at,
+            });
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// Sets the code hash of the pending upgrade once its delay has passed.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager)]
+        pub fn execute_upgrade(&mut self) -> Result<(), PeerNftError> {
+            let pending = self
+                .pending_upgrade
+                .get()
+                .unwrap_or_default()
+                .ok_or(PeerNftError::NoPendingUpgrade)?;
+            if self.env().block_timestamp() < pending.executable_at {
+                return Err(PeerNftError::UpgradeNotReady);
+            }
+            // The code hash may have been deregistered since the proposal
+            if !self.allowed_code_hashes.contains(&pending.code_hash) {
+                return Err(PeerNftError::CodeHashNotAllowed);
+            }
+
+            self.pending_upgrade.set(&None);
+            ink::env::set_code_hash(&pending.code_hash).map_err(|_| PeerNftError::UpgradeFailed)?;
+            self.env().emit_event(UpgradeExecuted {
+                _code_hash: pending.code_hash,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_code_hash_allowed(&self, code_hash: [u8; 32]) -> bool {
+            self.allowed_code_hashes.contains(&code_hash)
+        }
+
+        /// Only admin
+        ///
+        /// Makes `code_hash` eligible for `propose_upgrade`.
+        #[ink(message)]
+        #[openbrush::modifiers(only_admin)]
+        pub fn register_code_hash(&mut self, code_hash: [u8; 32]) -> Result<(), PeerNftError> {
+            self.allowed_code_hashes.insert(&code_hash, &());
+            self.env().emit_event(CodeHashRegistered {
+                _code_hash: code_hash,
+            });
+            Ok(())
+        }
+
+        /// Only admin
+        #[ink(message)]
+        #[openbrush::modifiers(only_admin)]
+        pub fn deregister_code_hash(&mut self, code_hash: [u8; 32]) -> Result<(), PeerNftError> {
+            self.allowed_code_hashes.remove(&code_hash);
+            self.env().emit_event(CodeHashDeregistered {
+                _code_hash: code_hash,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn storage_version(&self) -> u32 {
+            self.storage_version.get().unwrap_or_default()
+        }
+
+        /// Only manager
+        ///
+        /// Brings storage written by older code from `from_version` up to `STORAGE_VERSION`.
+        /// Messages changing tokens or sale settings are refused until this has run.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager)]
+        pub fn migrate(&mut self, from_version: u32) -> Result<(), PeerNftError> {
+            let storage_version = self.storage_version.get().unwrap_or_default();
+            if from_version != storage_version || from_version >= STORAGE_VERSION {
+                return Err(PeerNftError::InvalidStorageVersion);
+            }
+
+            // 0 -> 1: `upgrade_delay` was introduced
+            let upgrade_delay = self.upgrade_delay.get().unwrap_or_default();
+            if from_version < 1 && upgrade_delay < MIN_UPGRADE_DELAY {
+                self.upgrade_delay.set(&DEFAULT_UPGRADE_DELAY);
+            }
+            // 0 -> 1: roles added since the first deployment are only set up by `new`, hand them
+            // to the migrating manager as `new` hands them to the deployer
+            if from_version < 1 {
+                let caller = self.env().caller();
+                self._set_role_admin(MINTER, MINTER_ADMIN);
+                for role in [MINTER_ADMIN, MINTER, ADMIN, PAUSER, RECOVERY] {
+                    self._setup_role(role, caller);
+                }
+            }
+
+            self.storage_version.set(&STORAGE_VERSION);
+            self.env().emit_event(StorageMigrated {
+                _from_version: from_version,
+                _to_version: STORAGE_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Only manager
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager)]
+        pub fn cancel_upgrade(&mut self) -> Result<(), PeerNftError> {
+            let pending = self
+                .pending_upgrade
+                .get()
+                .unwrap_or_default()
+                .ok_or(PeerNftError::NoPendingUpgrade)?;
+            self.pending_upgrade.set(&None);
+            self.env().emit_event(UpgradeCancelled {
+                _code_hash: pending.code_hash,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn default_royalty(&self) -> Option<Royalty> {
+            self.default_royalty.get().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn token_royalty(&self, id: Id) -> Option<Royalty> {
+            self.token_royalties.get(&id)
+        }
+
+        /// Returns the royalty receiver of `id` and the amount owed to it for a sale at
+        /// `sale_price`, or `None` if no royalty applies.
+        #[ink(message)]
+        pub fn royalty_info(&self, id: Id, sale_price: Balance) -> Option<(AccountId, Balance)> {
+            let royalty = self
+                .token_royalties
+                .get(&id)
+                .or_else(|| self.default_royalty.get().unwrap_or_default())?;
+            Some((royalty.receiver, royalty.amount(sale_price)))
+        }
+
+        /// Only manager
+        ///
+        /// `None` removes the collection default royalty.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_default_royalty(&mut self, royalty: Option<Royalty>) -> Result<(), PeerNftError> {
+            if let Some(royalty) = &royalty {
+                self._check_royalty(royalty)?;
+            }
+            self.default_royalty.set(&royalty);
+            self.env().emit_event(RoyaltySet {
+                _id: None,
+                _royalty: royalty,
+            });
+            Ok(())
+        }
+
+        /// Only manager
+        ///
+        /// `None` removes the royalty of `id`, falling back to the collection default.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_token_royalty(
+            &mut self,
+            id: Id,
+            royalty: Option<Royalty>,
+        ) -> Result<(), PeerNftError> {
+            self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            match &royalty {
+                Some(royalty) => {
+                    self._check_royalty(royalty)?;
+                    self.token_royalties.insert(&id, royalty);
+                }
+                None => self.token_royalties.remove(&id),
+            }
+            self.env().emit_event(RoyaltySet {
+                _id: Some(id),
+                _royalty: royalty,
+            });
+            Ok(())
+        }
+
+        /// A royalty has to leave room for the platform fee, otherwise every sale would fail.
+        fn _check_royalty(&self, royalty: &Royalty) -> Result<(), PeerNftError> {
+            let platform_fee = self.platform_fee_basis_points.get().unwrap_or_default();
+            if royalty.basis_points as u32 + platform_fee as u32 > MAX_ROYALTY_BASIS_POINTS as u32 {
+                return Err(PeerNftError::InvalidRoyalty);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn listing(&self, id: Id) -> Option<Listing> {
+            self.listings.get(&id)
+        }
+
+        #[ink(message)]
+        pub fn platform_fee_basis_points(&self) -> u16 {
+            self.platform_fee_basis_points.get().unwrap_or_default()
+        }
+
+        /// Only manager
+        ///
+        /// The fee and the collection default royalty together can't exceed 100%.
+        #[ink(message)]
+        #[openbrush::modifiers(only_manager, when_migrated)]
+        pub fn set_platform_fee(&mut self, basis_points: u16) -> Result<(), PeerNftError> {
+            let default_royalty = self
+                .default_royalty
+                .get()
+                .unwrap_or_default()
+                .map_or(0, |royalty| royalty.basis_points);
+            if basis_points as u32 + default_royalty as u32 > MAX_ROYALTY_BASIS_POINTS as u32 {
+                return Err(PeerNftError::InvalidFee);
+            }
+            self.platform_fee_basis_points.set(&basis_points);
+            self.env().emit_event(PlatformFeeSet {
+                _basis_points: basis_points,
+            });
+            Ok(())
+        }
+
+        /// Offers `id` for sale at `price`, replacing any previous listing. Only the owner can list.
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Transfer))]
+        pub fn list(&mut self, id: Id, price: Balance) -> Result<(), PeerNftError> {
+            let caller = self.env().caller();
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if owner != caller {
+                return Err(PeerNftError::NotTokenOwner);
+            }
+            if price == 0 {
+                return Err(PeerNftError::InvalidPrice);
+            }
+            if !self.is_transferable(id.clone()) {
+                return Err(PeerNftError::NotTransferable);
+            }
+
+            self.listings.insert(
+                &id,
+                &Listing {
+                    seller: caller,
+                    price,
+                },
+            );
+            self.env().emit_event(Listed {
+                _id: id,
+                _seller: caller,
+                _price: price,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated)]
+        pub fn cancel_listing(&mut self, id: Id) -> Result<(), PeerNftError> {
+            let listing = self.listings.get(&id).ok_or(PeerNftError::NotListed)?;
+            if listing.seller != self.env().caller() {
+                return Err(PeerNftError::NotTokenOwner);
+            }
+
+            self.listings.remove(&id);
+            self.env().emit_event(ListingCancelled { _id: id });
+            Ok(())
+        }
+
+        /// Buys a listed token paying exactly its price. The seller receives the price minus the
+        /// royalty and the platform fee.
+        #[ink(message, payable)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Transfer))]
+        pub fn buy(&mut self, id: Id) -> Result<(), PeerNftError> {
+            let buyer = self.env().caller();
+            let listing = self.listings.get(&id).ok_or(PeerNftError::NotListed)?;
+            if self.env().transferred_value() != listing.price {
+                return Err(PeerNftError::IncorrectPayment);
+            }
+
+            // Moves the token to the buyer and drops the listing
+            self._transfer(buyer, id.clone())?;
+            self._pay_sale(&id, listing.seller, listing.price)?;
+
+            self.env().emit_event(Sold {
+                _id: id,
+                _seller: listing.seller,
+                _buyer: buyer,
+                _price: listing.price,
+            });
+            Ok(())
+        }
+
+        /// Splits `price` between the royalty receiver of `id`, `sale_proceeds` and `seller`.
+        fn _pay_sale(
+            &mut self,
+            id: &Id,
+            seller: AccountId,
+            price: Balance,
+        ) -> Result<(), PeerNftError> {
+            let platform_fee =
+                basis_points_of(price, self.platform_fee_basis_points.get().unwrap_or_default());
+            // A token royalty set before the platform fee was raised may no longer fit next to
+            // it, cut the royalty down instead of blocking every sale of the token
+            let max_royalty = price.saturating_sub(platform_fee);
+            let royalty = self
+                .royalty_info(id.clone(), price)
+                .filter(|(receiver, _)| *receiver != seller)
+                .map(|(receiver, amount)| (receiver, amount.min(max_royalty)));
+            let royalty_amount = royalty.map_or(0, |(_, amount)| amount);
+            let seller_amount = price
+                .checked_sub(platform_fee)
+                .and_then(|rest| rest.checked_sub(royalty_amount))
+                .ok_or(PeerNftError::FeesExceedPrice)?;
+
+            self._add_sale_proceeds(platform_fee);
+            if let Some((receiver, amount)) = royalty {
+                if amount > 0 && self.env().transfer(receiver, amount).is_err() {
+                    return Err(PeerNftError::TransferFailed);
+                }
+            }
+            if seller_amount > 0 && self.env().transfer(seller, seller_amount).is_err() {
+                return Err(PeerNftError::TransferFailed);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn auction(&self, id: Id) -> Option<Auction> {
+            self.auctions.get(&id)
+        }
+
+        /// Starts an auction of `id` ending `duration` milliseconds from now. The token is held
+        /// by the contract until the auction is settled or cancelled. Only the owner can start it.
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Transfer))]
+        pub fn create_auction(
+            &mut self,
+            id: Id,
+            reserve_price: Balance,
+            min_bid_increment: Balance,
+            duration: Timestamp,
+        ) -> Result<(), PeerNftError> {
+            let caller = self.env().caller();
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if owner != caller {
+                return Err(PeerNftError::NotTokenOwner);
+            }
+            if duration == 0 || duration > MAX_AUCTION_DURATION {
+                return Err(PeerNftError::InvalidDuration);
+            }
+            if !self.is_transferable(id.clone()) {
+                return Err(PeerNftError::NotTransferable);
+            }
+
+            let end_time = self.env().block_timestamp().saturating_add(duration);
+            self._transfer(self.env().account_id(), id.clone())?;
+            self.auctions.insert(
+                &id,
+                &Auction {
+                    seller: caller,
+                    reserve_price,
+                    min_bid_increment,
+                    end_time,
+                    highest_bidder: None,
+                    highest_bid: 0,
+                },
+            );
+            self.env().emit_event(AuctionCreated {
+                _id: id,
+                _seller: caller,
+                _reserve_price: reserve_price,
+                _end_time: end_time,
+            });
+            Ok(())
+        }
+
+        /// Bids the transferred value on `id`. The previous highest bid is refunded, and a bid in
+        /// the last `AUCTION_EXTENSION` of the auction extends it.
+        #[ink(message, payable)]
+        #[openbrush::modifiers(when_migrated)]
+        pub fn bid(&mut self, id: Id) -> Result<(), PeerNftError> {
+            let bidder = self.env().caller();
+            let amount = self.env().transferred_value();
+            let now = self.env().block_timestamp();
+            let mut auction = self.auctions.get(&id).ok_or(PeerNftError::AuctionNotFound)?;
+            if now >= auction.end_time {
+                return Err(PeerNftError::AuctionEnded);
+            }
+            if bidder == auction.seller {
+                return Err(PeerNftError::SellerCannotBid);
+            }
+            let min_bid = match auction.highest_bidder {
+                Some(_) => auction.highest_bid.saturating_add(auction.min_bid_increment.max(1)),
+                None => auction.reserve_price,
+            };
+            if amount < min_bid {
+                return Err(PeerNftError::BidTooLow);
+            }
+
+            let outbid = auction.highest_bidder.replace(bidder).map(|b| (b, auction.highest_bid));
+            auction.highest_bid = amount;
+            if auction.end_time - now < AUCTION_EXTENSION {
+                auction.end_time = now + AUCTION_EXTENSION;
+            }
+            self.auctions.insert(&id, &auction);
+
+            if let Some((outbid_bidder, outbid_amount)) = outbid {
+                if self.env().transfer(outbid_bidder, outbid_amount).is_err() {
+                    return Err(PeerNftError::TransferFailed);
+                }
+            }
+            self.env().emit_event(BidPlaced {
+                _id: id,
+                _bidder: bidder,
+                _amount: amount,
+                _end_time: auction.end_time,
+            });
+            Ok(())
+        }
+
+        /// Ends the auction of `id` once its end time has passed. The token goes to the highest
+        /// bidder and the bid is paid like a `buy`, or back to the seller if nobody bid.
+        /// Anybody can settle.
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Transfer))]
+        pub fn settle_auction(&mut self, id: Id) -> Result<(), PeerNftError> {
+            let auction = self.auctions.get(&id).ok_or(PeerNftError::AuctionNotFound)?;
+            if self.env().block_timestamp() < auction.end_time {
+                return Err(PeerNftError::AuctionNotEnded);
+            }
+
+            self.auctions.remove(&id);
+            match auction.highest_bidder {
+                Some(winner) => {
+                    self._transfer(winner, id.clone())?;
+                    self._pay_sale(&id, auction.seller, auction.highest_bid)?;
+                }
+                None => self._transfer(auction.seller, id.clone())?,
+            }
+            self.env().emit_event(AuctionSettled {
+                _id: id,
+                _winner: auction.highest_bidder,
+                _amount: auction.highest_bid,
+            });
+            Ok(())
+        }
+
+        /// Returns the token to the seller. Only possible before the first bid.
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Transfer))]
+        pub fn cancel_auction(&mut self, id: Id) -> Result<(), PeerNftError> {
+            let auction = self.auctions.get(&id).ok_or(PeerNftError::AuctionNotFound)?;
+            if auction.seller != self.env().caller() {
+                return Err(PeerNftError::NotTokenOwner);
+            }
+            if auction.highest_bidder.is_some() {
+                return Err(PeerNftError::AuctionHasBids);
+            }
+
+            self.auctions.remove(&id);
+            self._transfer(auction.seller, id.clone())?;
+            self.env().emit_event(AuctionCancelled { _id: id });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn offer(&self, id: Id, bidder: AccountId) -> Option<Offer> {
+            self.offers.get(&(id, bidder))
+        }
+
+        /// Escrows the transferred value as an offer on `id`, listed or not. A second offer on the
+        /// same token adds to the escrowed amount and replaces the expiry.
+        #[ink(message, payable)]
+        #[openbrush::modifiers(when_migrated)]
+        pub fn make_offer(&mut self, id: Id, expiry: Timestamp) -> Result<(), PeerNftError> {
+            let bidder = self.env().caller();
+            let amount = self.env().transferred_value();
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if owner == bidder {
+                return Err(PeerNftError::SelfOffer);
+            }
+            if amount == 0 {
+                return Err(PeerNftError::InvalidPrice);
+            }
+            if !self.is_transferable(id.clone()) {
+                return Err(PeerNftError::NotTransferable);
+            }
+            if expiry <= self.env().block_timestamp() {
+                return Err(PeerNftError::OfferExpired);
+            }
+
+            let key = (id.clone(), bidder);
+            let previous = self.offers.get(&key).map_or(0, |offer| offer.amount);
+            let offer = Offer {
+                amount: previous + amount,
+                expiry,
+            };
+            self.offers.insert(&key, &offer);
+            self.env().emit_event(OfferMade {
+                _id: id,
+                _bidder: bidder,
+                _amount: offer.amount,
+                _expiry: expiry,
+            });
+            Ok(())
+        }
+
+        /// Refunds the caller's offer on `id`. Offers on burned tokens can only be withdrawn.
+        #[ink(message)]
+        pub fn withdraw_offer(&mut self, id: Id) -> Result<(), PeerNftError> {
+            let bidder = self.env().caller();
+            let key = (id.clone(), bidder);
+            let offer = self.offers.get(&key).ok_or(PeerNftError::OfferNotFound)?;
+
+            self.offers.remove(&key);
+            if self.env().transfer(bidder, offer.amount).is_err() {
+                return Err(PeerNftError::TransferFailed);
+            }
+            self.env().emit_event(OfferWithdrawn {
+                _id: id,
+                _bidder: bidder,
+            });
+            Ok(())
+        }
+
+        /// Sells `id` to `bidder` for its escrowed offer, paid out like a `buy`. Only the owner
+        /// can accept.
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated, when_not_paused(PausableOperation::Transfer))]
+        pub fn accept_offer(&mut self, id: Id, bidder: AccountId) -> Result<(), PeerNftError> {
+            let seller = self.env().caller();
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if owner != seller {
+                return Err(PeerNftError::NotTokenOwner);
+            }
+            let key = (id.clone(), bidder);
+            let offer = self.offers.get(&key).ok_or(PeerNftError::OfferNotFound)?;
+            if self.env().block_timestamp() >= offer.expiry {
+                return Err(PeerNftError::OfferExpired);
+            }
+
+            self.offers.remove(&key);
+            self._transfer(bidder, id.clone())?;
+            self._pay_sale(&id, seller, offer.amount)?;
+            self.env().emit_event(OfferAccepted {
+                _id: id,
+                _seller: seller,
+                _bidder: bidder,
+                _amount: offer.amount,
+            });
+            Ok(())
+        }
+
+        /// Returns the current user of `id`, `None` if there is none or the rental expired.
+        #[ink(message)]
+        pub fn user_of(&self, id: Id) -> Option<AccountId> {
+            self.token_users
+                .get(&id)
+                .filter(|user| self.env().block_timestamp() < user.expires)
+                .map(|user| user.user)
+        }
+
+        /// Returns the timestamp the rental of `id` expires at, `0` if there is no user.
+        #[ink(message)]
+        pub fn user_expires(&self, id: Id) -> Timestamp {
+            self.token_users.get(&id).map_or(0, |user| user.expires)
+        }
+
+        /// Rents `id` to `user` until `expires_at`. Only the owner or an approved account can set
+        /// it. The user is reset when the token is transferred.
+        #[ink(message)]
+        #[openbrush::modifiers(when_migrated)]
+        pub fn set_user(
+            &mut self,
+            id: Id,
+            user: AccountId,
+            expires_at: Timestamp,
+        ) -> Result<(), PeerNftError> {
+            let caller = self.env().caller();
+            let owner = self.owner_of(id.clone()).ok_or(PeerNftError::TokenNotFound)?;
+            if owner != caller && !self._allowance(&owner, &caller, &Some(&id)) {
+                return Err(PeerNftError::NotApproved);
+            }
+
+            self.token_users.insert(
+                &id,
+                &TokenUser {
+                    user,
+                    expires: expires_at,
+                },
+            );
+            self.env().emit_event(UpdateUser {
+                _id: id,
+                _user: Some(user),
+                _expires: expires_at,
+            });
+            Ok(())
+        }
+
+        /// Returns every token id of `owner`.
+        ///
+        /// Reads the whole owner index, so its cost grows with the balance of `owner` without
+        /// bound. Only meant for off-chain queries, other contracts and callers that can't
+        /// afford an unbounded read should use `nft_ids_of_page`.
+        #[ink(message)]
+        pub fn nft_ids_of(&self, owner: AccountId) -> Option<Vec<u32>> {
+            let ids_vec = (0..self.balance_of(owner))
+                .filter_map(|index| match self.owners_token_by_index(owner, index as u128) {
+                    Ok(Id::U32(n)) => Some(n),
+                    _ => None,
+                })
+                .collect();
+            Some(ids_vec)
+        }
+
+        /// Returns up to `limit` token ids of `owner`, starting at `offset` in the owner index.
+        ///
+        /// `limit` is capped at `MAX_PAGE_SIZE`.
+        #[ink(message)]
+        pub fn nft_ids_of_page(&self, owner: AccountId, offset: u32, limit: u32) -> Vec<Id> {
+            let balance = self.balance_of(owner);
+            let end = balance.min(offset.saturating_add(limit.min(MAX_PAGE_SIZE)));
+
+            let mut ids_vec: Vec<Id> = Vec::new();
+            for index in offset..end {
+                if let Ok(id) = self.owners_token_by_index(owner, index as u128) {
+                    ids_vec.push(id);
+                }
+            }
+            ids_vec
+        }
+    }
+
+    ////////////////////////////////////////////// Off Chain Tests /////////////////////////////////////////
+
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+        use ink::codegen::Env;
+
+        /// We test if the default constructor does its job.
+        #[ink::test]
+        fn constructor_works_name() {
+            let PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let collection_id = PeerNftcontract.collection_id();
+            let key = String::from("name");
+
+            assert_eq!(
+                PeerNftcontract
+                    .get_attribute(collection_id, key)
+                    .unwrap(),
+                b"My First NFT"
+            );
+        }
+
+        #[ink::test]
+        fn constructor_works_symbol() {
+            let PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let collection_id = PeerNftcontract.collection_id();
+            let key = String::from("symbol");
+            assert_eq!(
+                PeerNftcontract
+                    .get_attribute(collection_id, key)
+                    .unwrap(),
+                "Nova".as_bytes()
+            );
+        }
+
+        #[ink::test]
+        fn check_admin_role() {
+            let PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            assert_eq!(PeerNftcontract.get_role_admin(0), 0);
+        }
+
+        #[ink::test]
+        fn check_what_admin_role_contains() {
+            let PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            assert_eq!(PeerNftcontract.access.admin_roles.get(0), None);
+        }
+
+        #[ink::test]
+        fn check_do_admin_role_contains() {
+            let PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            assert_eq!(PeerNftcontract.access.admin_roles.contains(0), false);
+        }
+
+        #[ink::test]
+        fn deployer_can_mint() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.bob));
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("uri"))
+            );
+        }
+
+        #[ink::test]
+        fn unprivileged_caller_cannot_mint() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::NotMinter)
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), None);
+            assert_eq!(PeerNftcontract.get_token_uri(Id::U32(0)), None);
+        }
+
+        #[ink::test]
+        fn minter_role_can_be_granted_and_revoked() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(PeerNftcontract.get_role_admin(MINTER), MINTER_ADMIN);
+            assert_eq!(PeerNftcontract.grant_role(MINTER, accounts.bob), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            // A minter is not a manager.
+            assert_eq!(
+                PeerNftcontract.manager_mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::NotManager)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.revoke_role(MINTER, accounts.bob), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::NotMinter)
+            );
+        }
+
+        #[ink::test]
+        fn only_minter_admin_can_grant_minter() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(PeerNftcontract.grant_role(MANAGER, accounts.bob), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.grant_role(MINTER, accounts.charlie),
+                Err(AccessControlError::MissingRole)
+            );
+        }
+
+        #[ink::test]
+        fn public_sale_mint_works() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(PeerNftcontract.set_mint_price(100), Ok(()));
+            assert_eq!(PeerNftcontract.set_sale_phase(SalePhase::Public), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.bob));
+            assert_eq!(PeerNftcontract.public_mints_of(accounts.bob), 1);
+            assert_eq!(PeerNftcontract.sale_proceeds(), 100);
+        }
+
+        #[ink::test]
+        fn public_sale_rejects_wrong_payment() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(PeerNftcontract.set_mint_price(100), Ok(()));
+            assert_eq!(PeerNftcontract.set_sale_phase(SalePhase::Public), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(99);
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::IncorrectPayment)
+            );
+            assert_eq!(PeerNftcontract.public_mints_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn public_sale_enforces_per_account_limit() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(PeerNftcontract.set_max_mints_per_account(1), Ok(()));
+            assert_eq!(PeerNftcontract.set_sale_phase(SalePhase::Public), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::MintLimitReached)
+            );
+        }
+
+        #[ink::test]
+        fn max_supply_caps_all_mints() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(PeerNftcontract.set_max_supply(1), Ok(()));
+            assert_eq!(
+                PeerNftcontract.manager_mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::SupplyExhausted)
+            );
+        }
+
+        #[ink::test]
+        fn withdraw_proceeds_pays_treasury() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(PeerNftcontract.set_mint_price(100), Ok(()));
+            assert_eq!(PeerNftcontract.set_sale_phase(SalePhase::Public), Ok(()));
+            assert_eq!(
+                PeerNftcontract.withdraw_proceeds(),
+                Err(PeerNftError::TreasuryNotSet)
+            );
+            assert_eq!(PeerNftcontract.set_treasury(accounts.eve), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.withdraw_proceeds(),
+                Err(PeerNftError::NotManager)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 100);
+            let before =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.eve)
+                    .unwrap();
+            assert_eq!(PeerNftcontract.withdraw_proceeds(), Ok(100));
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.eve)
+                    .unwrap(),
+                before + 100
+            );
+            assert_eq!(PeerNftcontract.sale_proceeds(), 0);
+        }
+
+        #[ink::test]
+        fn burn_of_missing_token_returns_error() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.burn(accounts.alice, Id::U32(0)),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert_eq!(
+                PeerNftcontract.manager_burn(accounts.alice, Id::U32(0), "loc".into(), "uri".into()),
+                Err(PeerNftError::TokenNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn burn_rejects_account_other_than_owner() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.manager_burn(accounts.charlie, Id::U32(0), "loc".into(), "uri".into()),
+                Err(PeerNftError::NotTokenOwner)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.burn(accounts.charlie, Id::U32(0)),
+                Err(PSP34Error::Custom(String::from("NotTokenOwner")))
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.bob));
+            assert_eq!(PeerNftcontract.balance_of(accounts.bob), 1);
+        }
+
+        #[ink::test]
+        fn owner_burn_clears_uri_and_location() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.burn(accounts.bob, Id::U32(0)), Ok(()));
+            assert_eq!(PeerNftcontract.get_token_uri(Id::U32(0)), None);
+            assert_eq!(PeerNftcontract.get_token_location(Id::U32(0)), None);
+        }
+
+        #[ink::test]
+        fn nft_ids_of_paginates_owner_tokens() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            for account in [accounts.bob, accounts.charlie, accounts.bob, accounts.bob] {
+                assert_eq!(
+                    PeerNftcontract.mint(account, "loc".into(), "uri".into()),
+                    Ok(())
+                );
+            }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.burn(accounts.bob, Id::U32(2)), Ok(()));
+
+            assert_eq!(
+                PeerNftcontract.nft_ids_of_page(accounts.bob, 0, 10),
+                vec![Id::U32(0), Id::U32(3)]
+            );
+            assert_eq!(PeerNftcontract.nft_ids_of_page(accounts.bob, 1, 1), vec![Id::U32(3)]);
+            assert_eq!(PeerNftcontract.nft_ids_of_page(accounts.bob, 2, 10), vec![]);
+            assert_eq!(
+                PeerNftcontract.nft_ids_of_page(accounts.charlie, 0, 10),
+                vec![Id::U32(1)]
+            );
+            assert_eq!(PeerNftcontract.nft_ids_of(accounts.bob), Some(vec![0, 3]));
+        }
+
+        #[ink::test]
+        fn manager_batch_mint_works() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let events_before = ink::env::test::recorded_events().count();
+
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![
+                    (accounts.bob, "loc0".into(), "uri0".into()),
+                    (accounts.charlie, "loc1".into(), "uri1".into()),
+                ]),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.bob));
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(1)), Some(accounts.charlie));
+            assert_eq!(
+                PeerNftcontract.get_token_location(Id::U32(1)),
+                Some(String::from("loc1"))
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(1)),
+                Some(String::from("uri1"))
+            );
+            // One Transfer, SetTokenUri and SetTokenLocation per token
+            assert_eq!(ink::env::test::recorded_events().count(), events_before + 3 * 2);
+        }
+
+        #[ink::test]
+        fn manager_batch_mint_is_all_or_nothing() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            let long_uri = vec![b'a'; MAX_TOKEN_URI_LEN + 1];
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![
+                    (accounts.bob, "loc0".into(), "uri0".into()),
+                    (accounts.bob, "loc1".into(), long_uri),
+                ]),
+                Err(PeerNftError::UriTooLong)
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), None);
+
+            assert_eq!(PeerNftcontract.set_max_supply(1), Ok(()));
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![
+                    (accounts.bob, "loc0".into(), "uri0".into()),
+                    (accounts.bob, "loc1".into(), "uri1".into()),
+                ]),
+                Err(PeerNftError::SupplyExhausted)
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), None);
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![]),
+                Err(PeerNftError::InvalidBatchSize)
+            );
+        }
+
+        #[ink::test]
+        fn batch_update_token_data_works() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![
+                    (accounts.bob, "loc0".into(), "uri0".into()),
+                    (accounts.bob, "loc1".into(), "uri1".into()),
+                ]),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.batch_update_token_uri(vec![
+                    (Id::U32(0), "new uri0".into()),
+                    (Id::U32(1), "new uri1".into()),
+                ]),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.batch_update_token_location(vec![(Id::U32(1), "new loc1".into())]),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.batch_update_token_data(vec![(
+                    Id::U32(0),
+                    "loc0 v2".into(),
+                    "uri0 v2".into()
+                )]),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("uri0 v2"))
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_location(Id::U32(0)),
+                Some(String::from("loc0 v2"))
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(1)),
+                Some(String::from("new uri1"))
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_location(Id::U32(1)),
+                Some(String::from("new loc1"))
+            );
+        }
+
+        #[ink::test]
+        fn batch_update_is_all_or_nothing() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.batch_update_token_uri(vec![
+                    (Id::U32(0), "new uri".into()),
+                    (Id::U32(1), "new uri".into()),
+                ]),
+                Err(PeerNftError::TokenNotFound)
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("uri"))
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.batch_update_token_location(vec![(Id::U32(0), "loc".into())]),
+                Err(PeerNftError::NotManager)
+            );
+        }
+
+        #[ink::test]
+        fn token_history_records_every_value() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert_eq!(PeerNftcontract.update_token_uri(Id::U32(0), "uri v2".into()), Ok(()));
+            assert_eq!(PeerNftcontract.update_token_uri(Id::U32(0), "uri v3".into()), Ok(()));
+
+            assert_eq!(PeerNftcontract.token_uri_history_len(Id::U32(0)), 3);
+            assert_eq!(PeerNftcontract.token_location_history_len(Id::U32(0)), 1);
+
+            let history = PeerNftcontract.token_uri_history(Id::U32(0), 0, 10);
+            assert_eq!(history.len(), 3);
+            assert_eq!(history[0].value, String::from("uri"));
+            assert_eq!(history[0].block_number, 0);
+            assert_eq!(history[0].updater, accounts.alice);
+            assert_eq!(history[2].value, String::from("uri v3"));
+            assert_eq!(history[2].block_number, 1);
+
+            let page = PeerNftcontract.token_uri_history(Id::U32(0), 1, 1);
+            assert_eq!(page.len(), 1);
+            assert_eq!(page[0].value, String::from("uri v2"));
+            assert_eq!(
+                PeerNftcontract.token_location_history(Id::U32(0), 0, 10)[0].value,
+                String::from("loc")
+            );
+        }
+
+        #[ink::test]
+        fn geo_location_is_validated_and_stored() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            let location = Location {
+                lat_e7: -338_567_844,
+                lng_e7: 1_512_152_967,
+                altitude_cm: Some(1200),
+                accuracy_m: 5,
+            };
+            assert_eq!(
+                PeerNftcontract.set_token_geo_location(
+                    Id::U32(0),
+                    Location {
+                        lat_e7: 900_000_001,
+                        ..location.clone()
+                    }
+                ),
+                Err(PeerNftError::InvalidLocation)
+            );
+            assert_eq!(
+                PeerNftcontract.set_token_geo_location(Id::U32(0), location.clone()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_geo_location(Id::U32(0)),
+                Some(location)
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_location(Id::U32(0)),
+                Some(String::from("-33.8567844,151.2152967"))
+            );
+
+            assert_eq!(
+                PeerNftcontract.update_token_location(Id::U32(0), "Sydney".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.get_token_geo_location(Id::U32(0)), None);
+        }
+
+        #[ink::test]
+        fn geohash_matches_reference() {
+            assert_eq!(
+                Location::geohash(576_491_100, 104_074_400, 6),
+                b"u4pruy".to_vec()
+            );
+            assert_eq!(
+                Location::parse_coordinates("57.64911, 10.40744"),
+                Some((576_491_100, 104_074_400))
+            );
+            assert_eq!(Location::parse_coordinates("-0.5,-0.25"), Some((-5_000_000, -2_500_000)));
+            assert_eq!(Location::parse_coordinates("Sydney"), None);
+            assert_eq!(Location::parse_coordinates("91.0,0.0"), None);
+        }
+
+        #[ink::test]
+        fn tokens_in_geohash_tracks_location_changes() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![
+                    (accounts.bob, "57.64911,10.40744".into(), "uri0".into()),
+                    (accounts.bob, "57.649,10.407".into(), "uri1".into()),
+                    (accounts.bob, "-33.8567844,151.2152967".into(), "uri2".into()),
+                    (accounts.bob, "Sydney".into(), "uri3".into()),
+                ]),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.tokens_in_geohash("u4pru".into(), 0, 10),
+                vec![Id::U32(0), Id::U32(1)]
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_geohash(Id::U32(0)),
+                Some(String::from("u4pruy"))
+            );
+            assert_eq!(PeerNftcontract.get_token_geohash(Id::U32(3)), None);
+
+            assert_eq!(
+                PeerNftcontract.update_token_location(Id::U32(0), "-33.8568,151.2153".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.tokens_in_geohash("u4pru".into(), 0, 10),
+                vec![Id::U32(1)]
+            );
+            assert_eq!(
+                PeerNftcontract.tokens_in_geohash("r3gx2".into(), 0, 10),
+                vec![Id::U32(2), Id::U32(0)]
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.burn(accounts.bob, Id::U32(2)), Ok(()));
+            assert_eq!(
+                PeerNftcontract.tokens_in_geohash("r3gx2".into(), 0, 10),
+                vec![Id::U32(0)]
+            );
+            assert_eq!(PeerNftcontract.geohash_token_count("r".into()), 1);
+        }
+
+        #[ink::test]
+        fn update_and_burn_emit_events() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            let events_before = ink::env::test::recorded_events().count();
+            assert_eq!(PeerNftcontract.update_token_uri(Id::U32(0), "uri v2".into()), Ok(()));
+
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            assert_eq!(events.len(), events_before + 1);
+            let decoded = <Event as scale::Decode>::decode(&mut &events[events_before].data[..])
+                .expect("encountered invalid contract event data buffer");
+            match decoded {
+                Event::UpdateTokenUri(UpdateTokenUri {
+                    _id,
+                    _old_token_uri,
+                    _token_uri,
+                }) => {
+                    assert_eq!(_id, Id::U32(0));
+                    assert_eq!(_old_token_uri, Some(String::from("uri")));
+                    assert_eq!(_token_uri, String::from("uri v2"));
+                }
+                _ => panic!("expected UpdateTokenUri event"),
+            }
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.burn(accounts.bob, Id::U32(0)), Ok(()));
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            let removed: Vec<_> = events[events_before + 1..]
+                .iter()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap())
+                .filter(|event| {
+                    matches!(event, Event::RemoveTokenUri(_) | Event::RemoveTokenLocation(_))
+                })
+                .collect();
+            assert_eq!(removed.len(), 2);
+        }
+
+        #[ink::test]
+        fn upgrade_is_timelocked() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let code_hash = [1u8; 32];
+
+            assert_eq!(
+                PeerNftcontract.execute_upgrade(),
+                Err(PeerNftError::NoPendingUpgrade)
+            );
+            assert_eq!(PeerNftcontract.register_code_hash(code_hash), Ok(()));
+            assert_eq!(PeerNftcontract.propose_upgrade(code_hash), Ok(()));
+            assert_eq!(
+                PeerNftcontract.pending_upgrade(),
+                Some(PendingUpgrade {
+                    code_hash,
+                    executable_at: DEFAULT_UPGRADE_DELAY,
+                })
+            );
+            assert_eq!(
+                PeerNftcontract.propose_upgrade(code_hash),
+                Err(PeerNftError::UpgradeAlreadyPending)
+            );
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(
+                DEFAULT_UPGRADE_DELAY - 1,
+            );
+            assert_eq!(
+                PeerNftcontract.execute_upgrade(),
+                Err(PeerNftError::UpgradeNotReady)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.cancel_upgrade(),
+                Err(PeerNftError::NotManager)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.cancel_upgrade(), Ok(()));
+            assert_eq!(PeerNftcontract.pending_upgrade(), None);
+        }
+
+        #[ink::test]
+        fn upgrade_requires_registered_code_hash() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let code_hash = [1u8; 32];
+
+            assert_eq!(
+                PeerNftcontract.propose_upgrade(code_hash),
+                Err(PeerNftError::CodeHashNotAllowed)
+            );
+
+            // A manager without ADMIN can't register code hashes
+            assert_eq!(PeerNftcontract.grant_role(MANAGER, accounts.bob), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.register_code_hash(code_hash),
+                Err(PeerNftError::NotAdmin)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.register_code_hash(code_hash), Ok(()));
+            assert!(PeerNftcontract.is_code_hash_allowed(code_hash));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.propose_upgrade(code_hash), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.deregister_code_hash(code_hash), Ok(()));
+            assert!(!PeerNftcontract.is_code_hash_allowed(code_hash));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(
+                DEFAULT_UPGRADE_DELAY,
+            );
+            assert_eq!(
+                PeerNftcontract.execute_upgrade(),
+                Err(PeerNftError::CodeHashNotAllowed)
+            );
+        }
+
+        #[ink::test]
+        fn upgrade_delay_has_a_minimum() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+
+            assert_eq!(PeerNftcontract.upgrade_delay(), DEFAULT_UPGRADE_DELAY);
+            assert_eq!(
+                PeerNftcontract.set_upgrade_delay(MIN_UPGRADE_DELAY - 1),
+                Err(PeerNftError::UpgradeDelayTooShort)
+            );
+            assert_eq!(PeerNftcontract.set_upgrade_delay(MIN_UPGRADE_DELAY), Ok(()));
+            assert_eq!(PeerNftcontract.upgrade_delay(), MIN_UPGRADE_DELAY);
+        }
+
+        #[ink::test]
+        fn messages_are_refused_until_migrated() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+
+            // Simulate new code deployed over storage written by the unversioned contract
+            PeerNftcontract.storage_version.set(&0);
+            PeerNftcontract.upgrade_delay.set(&0);
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::MigrationRequired)
+            );
+            assert_eq!(
+                PeerNftcontract.update_token_uri(Id::U32(0), "uri v2".into()),
+                Err(PeerNftError::MigrationRequired)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.transfer(accounts.charlie, Id::U32(0), vec![]),
+                Err(PSP34Error::Custom(String::from("MigrationRequired")))
+            );
+            assert_eq!(PeerNftcontract.migrate(0), Err(PeerNftError::NotManager));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                PeerNftcontract.migrate(1),
+                Err(PeerNftError::InvalidStorageVersion)
+            );
+            assert_eq!(PeerNftcontract.migrate(0), Ok(()));
+            assert_eq!(PeerNftcontract.storage_version(), STORAGE_VERSION);
+            assert_eq!(PeerNftcontract.upgrade_delay(), DEFAULT_UPGRADE_DELAY);
+            assert_eq!(
+                PeerNftcontract.migrate(0),
+                Err(PeerNftError::InvalidStorageVersion)
+            );
+
+            // Existing tokens survive the migration
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.bob));
+            assert_eq!(
+                PeerNftcontract.update_token_uri(Id::U32(0), "uri v2".into()),
+                Ok(())
+            );
+        }
+
+        /// Root storage layout of the contract before `storage_version` was introduced.
+        ///
+        /// Keeps the name of the contract so that its mappings get the same storage keys.
+        mod baseline {
+            use super::*;
+
+            #[ink::storage_item]
+            #[derive(Default)]
+            pub struct PeerNftcontract {
+                pub psp34: psp34::Data<Balances>,
+                pub access: access_control::Data<Members>,
+                pub metadata: metadata::Data,
+                pub token_uris: Mapping<Id, String>,
+                pub token_locations: Mapping<Id, String>,
+                pub next_id: u32,
+            }
+        }
+
+        #[ink::test]
+        fn baseline_storage_decodes_and_migrates() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let root_key = <PeerNftcontract as ink::storage::traits::StorageKey>::KEY;
+
+            // Roles live in openbrush's own mappings, grant MANAGER as the old constructor did
+            let mut deployed = PeerNftcontract::default();
+            deployed._init_with_admin(accounts.alice);
+            assert_eq!(deployed.grant_role(MANAGER, accounts.alice), Ok(()));
+
+            let mut old = baseline::PeerNftcontract {
+                next_id: 1,
+                ..Default::default()
+            };
+            old.token_uris.insert(&Id::U32(0), &String::from("uri"));
+            old.token_locations.insert(&Id::U32(0), &String::from("loc"));
+            ink::env::set_contract_storage(&root_key, &old);
+
+            let mut PeerNftcontract =
+                ink::env::get_contract_storage::<_, PeerNftcontract>(&root_key)
+                    .expect("baseline root should decode with the current layout")
+                    .expect("root should be stored");
+            assert_eq!(PeerNftcontract.storage_version(), 0);
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("uri"))
+            );
+            assert_eq!(
+                PeerNftcontract.manager_mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::MigrationRequired)
+            );
+            assert_eq!(PeerNftcontract.get_role_admin(MINTER), DEFAULT_ADMIN_ROLE);
+            assert!(!PeerNftcontract.has_role(PAUSER, accounts.alice));
+
+            assert_eq!(PeerNftcontract.migrate(0), Ok(()));
+            assert_eq!(PeerNftcontract.storage_version(), STORAGE_VERSION);
+            assert_eq!(PeerNftcontract.upgrade_delay(), DEFAULT_UPGRADE_DELAY);
+            assert_eq!(PeerNftcontract.get_role_admin(MINTER), MINTER_ADMIN);
+            for role in [MINTER_ADMIN, MINTER, ADMIN, PAUSER, RECOVERY] {
+                assert!(PeerNftcontract.has_role(role, accounts.alice));
+            }
+            assert_eq!(PeerNftcontract.pause(PausableOperation::Mint), Ok(()));
+            assert_eq!(PeerNftcontract.unpause(PausableOperation::Mint), Ok(()));
+            assert_eq!(
+                PeerNftcontract.mint(accounts.charlie, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.manager_mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(1)), Some(accounts.bob));
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(2)), Some(accounts.charlie));
+        }
+
+        #[ink::test]
+        fn baseline_storage_keeps_upgrades_timelocked() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let root_key = <PeerNftcontract as ink::storage::traits::StorageKey>::KEY;
+            let code_hash = [1u8; 32];
+
+            let mut deployed = PeerNftcontract::default();
+            deployed._init_with_admin(accounts.alice);
+            assert_eq!(deployed.grant_role(MANAGER, accounts.alice), Ok(()));
+            assert_eq!(deployed.grant_role(ADMIN, accounts.alice), Ok(()));
+            ink::env::set_contract_storage(&root_key, &baseline::PeerNftcontract::default());
+
+            let mut PeerNftcontract =
+                ink::env::get_contract_storage::<_, PeerNftcontract>(&root_key)
+                    .expect("baseline root should decode with the current layout")
+                    .expect("root should be stored");
+            assert_eq!(PeerNftcontract.storage_version(), 0);
+            assert_eq!(PeerNftcontract.upgrade_delay(), DEFAULT_UPGRADE_DELAY);
+
+            // Not migrated yet, the upgrade still has to wait for the default delay
+            assert_eq!(PeerNftcontract.register_code_hash(code_hash), Ok(()));
+            assert_eq!(PeerNftcontract.propose_upgrade(code_hash), Ok(()));
+            assert_eq!(
+                PeerNftcontract.pending_upgrade(),
+                Some(PendingUpgrade {
+                    code_hash,
+                    executable_at: DEFAULT_UPGRADE_DELAY,
+                })
+            );
+            assert_eq!(
+                PeerNftcontract.execute_upgrade(),
+                Err(PeerNftError::UpgradeNotReady)
+            );
+        }
+
+        #[ink::test]
+        fn paused_operations_are_refused() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.pause(PausableOperation::Mint), Ok(()));
+            assert!(PeerNftcontract.is_paused(PausableOperation::Mint));
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::Paused)
+            );
+            assert_eq!(
+                PeerNftcontract.manager_mint(accounts.bob, "loc".into(), "uri".into()),
+                Err(PeerNftError::Paused)
+            );
+
+            // Transfers keep working while only minting is paused
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.transfer(accounts.charlie, Id::U32(0), vec![]),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.pause(PausableOperation::Transfer),
+                Err(PeerNftError::NotPauser)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.pause_all(), Ok(()));
+            assert_eq!(
+                PeerNftcontract.update_token_uri(Id::U32(0), "uri v2".into()),
+                Err(PeerNftError::Paused)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                PeerNftcontract.transfer(accounts.bob, Id::U32(0), vec![]),
+                Err(PSP34Error::Custom(String::from("Paused")))
+            );
+            assert_eq!(
+                PeerNftcontract.burn(accounts.charlie, Id::U32(0)),
+                Err(PSP34Error::Custom(String::from("Paused")))
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.unpause_all(), Ok(()));
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn royalty_info_prefers_token_royalty() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![
+                    (accounts.bob, "loc0".into(), "uri0".into()),
+                    (accounts.bob, "loc1".into(), "uri1".into()),
+                ]),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.royalty_info(Id::U32(0), 1_000), None);
+
+            assert_eq!(
+                PeerNftcontract.set_default_royalty(Some(Royalty {
+                    receiver: accounts.eve,
+                    basis_points: 10_001,
+                })),
+                Err(PeerNftError::InvalidRoyalty)
+            );
+            assert_eq!(
+                PeerNftcontract.set_default_royalty(Some(Royalty {
+                    receiver: accounts.eve,
+                    basis_points: 250,
+                })),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.set_token_royalty(
+                    Id::U32(1),
+                    Some(Royalty {
+                        receiver: accounts.frank,
+                        basis_points: 1_000,
+                    })
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.royalty_info(Id::U32(0), 1_000),
+                Some((accounts.eve, 25))
+            );
+            assert_eq!(
+                PeerNftcontract.royalty_info(Id::U32(1), 1_005),
+                Some((accounts.frank, 100))
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.burn(accounts.bob, Id::U32(1)), Ok(()));
+            assert_eq!(PeerNftcontract.token_royalty(Id::U32(1)), None);
+        }
+
+        #[ink::test]
+        fn buy_pays_seller_royalty_and_fee() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let balance_of = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account)
+                    .unwrap()
+            };
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.set_default_royalty(Some(Royalty {
+                    receiver: accounts.eve,
+                    basis_points: 1_000,
+                })),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.set_platform_fee(MAX_ROYALTY_BASIS_POINTS + 1),
+                Err(PeerNftError::InvalidFee)
+            );
+            assert_eq!(PeerNftcontract.set_platform_fee(500), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                PeerNftcontract.list(Id::U32(0), 1_000),
+                Err(PeerNftError::NotTokenOwner)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.list(Id::U32(0), 1_000), Ok(()));
+            assert_eq!(PeerNftcontract.approve(accounts.django, Some(Id::U32(0)), true), Ok(()));
+            assert_eq!(PeerNftcontract.approve(accounts.frank, Some(Id::U32(0)), true), Ok(()));
+
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            let (bob_before, eve_before) = (balance_of(accounts.bob), balance_of(accounts.eve));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(999);
+            assert_eq!(
+                PeerNftcontract.buy(Id::U32(0)),
+                Err(PeerNftError::IncorrectPayment)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(PeerNftcontract.buy(Id::U32(0)), Ok(()));
+
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.charlie));
+            assert_eq!(PeerNftcontract.listing(Id::U32(0)), None);
+            // Every approval the seller gave on the sold token is gone
+            assert!(!PeerNftcontract.allowance(accounts.bob, accounts.django, Some(Id::U32(0))));
+            assert!(!PeerNftcontract.allowance(accounts.bob, accounts.frank, Some(Id::U32(0))));
+            assert_eq!(balance_of(accounts.bob), bob_before + 850);
+            assert_eq!(balance_of(accounts.eve), eve_before + 100);
+            assert_eq!(PeerNftcontract.sale_proceeds(), 50);
+            assert_eq!(PeerNftcontract.buy(Id::U32(0)), Err(PeerNftError::NotListed));
+        }
+
+        #[ink::test]
+        fn listing_is_dropped_on_transfer_and_cancel() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.manager_batch_mint(vec![
+                    (accounts.bob, "loc0".into(), "uri0".into()),
+                    (accounts.bob, "loc1".into(), "uri1".into()),
+                ]),
+                Ok(())
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.list(Id::U32(0), 1_000), Ok(()));
+            assert_eq!(PeerNftcontract.list(Id::U32(1), 1_000), Ok(()));
+
+            assert_eq!(
+                PeerNftcontract.transfer(accounts.charlie, Id::U32(0), vec![]),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.listing(Id::U32(0)), None);
+
+            assert_eq!(PeerNftcontract.cancel_listing(Id::U32(1)), Ok(()));
+            assert_eq!(PeerNftcontract.listing(Id::U32(1)), None);
+            assert_eq!(
+                PeerNftcontract.cancel_listing(Id::U32(1)),
+                Err(PeerNftError::NotListed)
+            );
+        }
+
+        #[ink::test]
+        fn auction_refunds_outbid_and_settles() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            let balance_of = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account)
+                    .unwrap()
+            };
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.create_auction(Id::U32(0), 100, 10, 60 * 60 * 1000),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(contract));
+
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(PeerNftcontract.bid(Id::U32(0)), Err(PeerNftError::SellerCannotBid));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(99);
+            assert_eq!(PeerNftcontract.bid(Id::U32(0)), Err(PeerNftError::BidTooLow));
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(PeerNftcontract.bid(Id::U32(0)), Ok(()));
+
+            let charlie_before = balance_of(accounts.charlie);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(105);
+            assert_eq!(PeerNftcontract.bid(Id::U32(0)), Err(PeerNftError::BidTooLow));
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(110);
+            assert_eq!(PeerNftcontract.bid(Id::U32(0)), Ok(()));
+            assert_eq!(balance_of(accounts.charlie), charlie_before + 100);
+
+            assert_eq!(
+                PeerNftcontract.settle_auction(Id::U32(0)),
+                Err(PeerNftError::AuctionNotEnded)
+            );
+            // A bid close to the end extends the auction
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(
+                60 * 60 * 1000 - 1,
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(120);
+            assert_eq!(PeerNftcontract.bid(Id::U32(0)), Ok(()));
+            assert_eq!(
+                PeerNftcontract.auction(Id::U32(0)).unwrap().end_time,
+                60 * 60 * 1000 - 1 + AUCTION_EXTENSION
+            );
+
+            let bob_before = balance_of(accounts.bob);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(
+                60 * 60 * 1000 - 1 + AUCTION_EXTENSION,
+            );
+            assert_eq!(PeerNftcontract.settle_auction(Id::U32(0)), Ok(()));
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.charlie));
+            assert_eq!(balance_of(accounts.bob), bob_before + 120);
+            assert_eq!(PeerNftcontract.auction(Id::U32(0)), None);
+        }
+
+        #[ink::test]
+        fn auction_without_bids_can_be_cancelled() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.create_auction(Id::U32(0), 100, 10, 0),
+                Err(PeerNftError::InvalidDuration)
+            );
+            assert_eq!(PeerNftcontract.create_auction(Id::U32(0), 100, 10, 1_000), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                PeerNftcontract.manager_burn(accounts.bob, Id::U32(0), "loc".into(), "uri".into()),
+                Err(PeerNftError::TokenInAuction)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.cancel_auction(Id::U32(0)), Ok(()));
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn auction_settles_when_royalty_and_fee_exceed_price() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            let balance_of = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account)
+                    .unwrap()
+            };
+            let royalty = |basis_points| Royalty {
+                receiver: accounts.eve,
+                basis_points,
+            };
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.set_default_royalty(Some(royalty(9_000))), Ok(()));
+            assert_eq!(
+                PeerNftcontract.set_platform_fee(2_000),
+                Err(PeerNftError::InvalidFee)
+            );
+            assert_eq!(PeerNftcontract.set_default_royalty(None), Ok(()));
+
+            // Token royalties can't be checked when the fee is raised later on
+            assert_eq!(
+                PeerNftcontract.set_token_royalty(Id::U32(0), Some(royalty(9_000))),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.set_platform_fee(2_000), Ok(()));
+            assert_eq!(
+                PeerNftcontract.set_token_royalty(Id::U32(0), Some(royalty(9_000))),
+                Err(PeerNftError::InvalidRoyalty)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.create_auction(Id::U32(0), 1_000, 10, 1_000), Ok(()));
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(PeerNftcontract.bid(Id::U32(0)), Ok(()));
+
+            let eve_before = balance_of(accounts.eve);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(AUCTION_EXTENSION);
+            assert_eq!(PeerNftcontract.settle_auction(Id::U32(0)), Ok(()));
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.charlie));
+            assert_eq!(balance_of(accounts.eve), eve_before + 800);
+            assert_eq!(PeerNftcontract.sale_proceeds(), 200);
+        }
+
+        #[ink::test]
+        fn offer_can_be_accepted_by_owner() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            let balance_of = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account)
+                    .unwrap()
+            };
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(300);
+            assert_eq!(PeerNftcontract.make_offer(Id::U32(0), 1_000), Ok(()));
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(200);
+            assert_eq!(PeerNftcontract.make_offer(Id::U32(0), 1_000), Ok(()));
+            assert_eq!(
+                PeerNftcontract.offer(Id::U32(0), accounts.charlie),
+                Some(Offer {
+                    amount: 500,
+                    expiry: 1_000,
+                })
+            );
+
+            assert_eq!(
+                PeerNftcontract.accept_offer(Id::U32(0), accounts.charlie),
+                Err(PeerNftError::NotTokenOwner)
+            );
+            let bob_before = balance_of(accounts.bob);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.accept_offer(Id::U32(0), accounts.charlie), Ok(()));
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.charlie));
+            assert_eq!(balance_of(accounts.bob), bob_before + 500);
+            assert_eq!(PeerNftcontract.offer(Id::U32(0), accounts.charlie), None);
+        }
+
+        #[ink::test]
+        fn offer_on_own_token_is_refused() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(300);
+            assert_eq!(
+                PeerNftcontract.make_offer(Id::U32(0), 1_000),
+                Err(PeerNftError::SelfOffer)
+            );
+            assert_eq!(PeerNftcontract.offer(Id::U32(0), accounts.bob), None);
+        }
+
+        #[ink::test]
+        fn offer_on_burned_token_can_only_be_withdrawn() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(contract, 1_000);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(300);
+            assert_eq!(PeerNftcontract.make_offer(Id::U32(0), 1_000), Ok(()));
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.burn(accounts.bob, Id::U32(0)), Ok(()));
+            assert_eq!(
+                PeerNftcontract.accept_offer(Id::U32(0), accounts.charlie),
+                Err(PeerNftError::TokenNotFound)
+            );
+
+            let charlie_before =
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(PeerNftcontract.withdraw_offer(Id::U32(0)), Ok(()));
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie)
+                    .unwrap(),
+                charlie_before + 300
+            );
+        }
+
+        #[ink::test]
+        fn user_expires_and_resets_on_transfer() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.set_user(Id::U32(0), accounts.eve, 1_000),
+                Err(PeerNftError::NotApproved)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.set_user(Id::U32(0), accounts.eve, 1_000), Ok(()));
+            assert_eq!(PeerNftcontract.user_of(Id::U32(0)), Some(accounts.eve));
+            assert_eq!(PeerNftcontract.user_expires(Id::U32(0)), 1_000);
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(PeerNftcontract.user_of(Id::U32(0)), None);
+
+            assert_eq!(PeerNftcontract.set_user(Id::U32(0), accounts.eve, 2_000), Ok(()));
+            assert_eq!(
+                PeerNftcontract.transfer(accounts.charlie, Id::U32(0), vec![]),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.user_of(Id::U32(0)), None);
+            assert_eq!(PeerNftcontract.user_expires(Id::U32(0)), 0);
+        }
+
+        #[ink::test]
+        fn non_transferable_tokens_can_only_be_burned() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.manager_mint_with_transferable(
+                    accounts.bob,
+                    "loc".into(),
+                    "uri".into(),
+                    false
+                ),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.set_collection_transferable(false), Ok(()));
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.set_collection_transferable(true), Ok(()));
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            assert!(!PeerNftcontract.is_transferable(Id::U32(0)));
+            assert!(!PeerNftcontract.is_transferable(Id::U32(1)));
+            assert!(PeerNftcontract.is_transferable(Id::U32(2)));
 
-            Ok(())
+            assert_eq!(
+                PeerNftcontract.recovery_transfer(accounts.charlie, Id::U32(0), 1),
+                Err(PeerNftError::NotTransferable)
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.transfer(accounts.charlie, Id::U32(1), vec![]),
+                Err(PSP34Error::Custom(String::from("NotTransferable")))
+            );
+            assert_eq!(
+                PeerNftcontract.list(Id::U32(1), 100),
+                Err(PeerNftError::NotTransferable)
+            );
+            assert_eq!(
+                PeerNftcontract.transfer(accounts.charlie, Id::U32(2), vec![]),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.burn(accounts.bob, Id::U32(0)), Ok(()));
         }
 
-        /// Only manager
-        #[ink(message)]
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn update_token_uri(&mut self, id: Id, _token_uri: String) -> Result<(), PSP34Error> {
-            self.token_uris.remove(&id);
-            self.token_uris.insert(&id, &_token_uri);
-            self._emit_updated_token_uri_event(id, _token_uri);
+        #[ink::test]
+        fn recovery_transfer_clears_every_approval() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
 
-            Ok(())
-        }
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(PeerNftcontract.approve(accounts.charlie, Some(Id::U32(0)), true), Ok(()));
+            assert_eq!(PeerNftcontract.approve(accounts.django, Some(Id::U32(0)), true), Ok(()));
 
-        pub fn _emit_set_token_location_event(&self, _id: Id, _token_location: String) {
-            self.env().emit_event(SetTokenLocation {
-                _id,
-                _token_location,
-            });
-        }
-        pub fn _emit_updated_token_location_event(&self, _id: Id, _token_location: String) {
-            self.env().emit_event(SetTokenLocation {
-                _id,
-                _token_location,
-            });
-        }
-        pub fn _emit_remove_token_location_event(&self, _id: Id) {}
+            // MANAGER alone can't claw tokens back
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.grant_role(MANAGER, accounts.eve), Ok(()));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(
+                PeerNftcontract.recovery_transfer(accounts.frank, Id::U32(0), 1),
+                Err(PeerNftError::NotRecovery)
+            );
 
-        /// Only manager
-        // #[openbrush::modifiers(only_role(MANAGER))]
-        fn set_token_location(
-            &mut self,
-            id: Id,
-            _token_location: String,
-        ) -> Result<(), PSP34Error> {
-            self.token_locations.insert(&id, &_token_location);
-            self._emit_set_token_location_event(id, _token_location);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                PeerNftcontract.recovery_transfer(accounts.frank, Id::U32(0), 0),
+                Err(PeerNftError::MissingReason)
+            );
+            assert_eq!(
+                PeerNftcontract.recovery_transfer(accounts.frank, Id::U32(0), 1),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.frank));
+            assert!(!PeerNftcontract.allowance(accounts.bob, accounts.charlie, Some(Id::U32(0))));
+            assert!(!PeerNftcontract.allowance(accounts.bob, accounts.django, Some(Id::U32(0))));
 
-            Ok(())
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            let decoded = <Event as scale::Decode>::decode(&mut &events[events.len() - 1].data[..])
+                .expect("encountered invalid contract event data buffer");
+            match decoded {
+                Event::ForcedTransfer(ForcedTransfer {
+                    _from, _reason, ..
+                }) => {
+                    assert_eq!(_from, accounts.bob);
+                    assert_eq!(_reason, 1);
+                }
+                _ => panic!("expected ForcedTransfer event"),
+            }
         }
 
-        #[ink(message)]
-        pub fn get_token_location(&self, id: Id) -> Option<String> {
-            self.token_locations.get(&id)
-        }
+        #[ink::test]
+        fn redeem_voucher_rejects_invalid_vouchers() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let voucher = MintVoucher {
+                recipient: accounts.bob,
+                token_uri: "uri".into(),
+                token_location: "loc".into(),
+                price: 100,
+                nonce: 7,
+                expiry: 1_000,
+            };
 
-        /// Only manager
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn remove_token_location(&mut self, id: Id) -> Result<(), PSP34Error> {
-            self.token_locations.remove(&id);
-            self._emit_remove_token_location_event(id);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(99);
+            assert_eq!(
+                PeerNftcontract.redeem_voucher(voucher.clone(), [0u8; 65]),
+                Err(PeerNftError::IncorrectPayment)
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(
+                PeerNftcontract.redeem_voucher(voucher.clone(), [0u8; 65]),
+                Err(PeerNftError::InvalidSignature)
+            );
+            assert_eq!(
+                PeerNftcontract.set_voucher_signer([2u8; 33], true),
+                Err(PeerNftError::NotManager)
+            );
 
-            Ok(())
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(
+                PeerNftcontract.redeem_voucher(voucher, [0u8; 65]),
+                Err(PeerNftError::VoucherExpired)
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), None);
+            assert!(!PeerNftcontract.is_voucher_nonce_used(7));
         }
 
-        /// Only manager
-        #[ink(message)]
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn update_token_location(
-            &mut self,
-            id: Id,
-            _token_location: String,
-        ) -> Result<(), PSP34Error> {
-            self.token_locations.remove(&id);
-            self.token_locations.insert(&id, &_token_location);
-            self._emit_updated_token_location_event(id, _token_location);
-
-            Ok(())
-        }
+        /// Signs the Blake2x256 hash of the SCALE encoded `message` with the secp256k1 key
+        /// `secret`, returns the compressed public key and the signature.
+        fn sign_encoded<T: scale::Encode>(secret: [u8; 32], message: &T) -> ([u8; 33], [u8; 65]) {
+            let secp = secp256k1::Secp256k1::new();
+            let secret_key = secp256k1::SecretKey::from_slice(&secret).unwrap();
+            let mut message_hash = [0u8; 32];
+            ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(message, &mut message_hash);
 
-        #[ink(message)]
-        pub fn mint(
-            &mut self,
-            account: AccountId,
-            _token_location: String,
-            _token_uri: String,
-        ) -> Result<(), PSP34Error> {
-            self.set_token_uri(Id::U32(self.next_id), _token_uri);
-            self.set_token_location(Id::U32(self.next_id), _token_location);
-            self._mint_to(account, Id::U32(self.next_id));
-            self.next_id += 1;
-            Ok(())
+            let message = secp256k1::Message::from_slice(&message_hash).unwrap();
+            let (recovery_id, compact) = secp
+                .sign_ecdsa_recoverable(&message, &secret_key)
+                .serialize_compact();
+            let mut signature = [0u8; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            let public_key = secp256k1::PublicKey::from_secret_key(&secp, &secret_key);
+            (public_key.serialize(), signature)
         }
 
-        /// Only manager
-        #[ink(message)]
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn manager_mint(
-            &mut self,
-            account: AccountId,
-            _token_location: String,
-            _token_uri: String,
-        ) -> Result<(), PSP34Error> {
-            self.set_token_uri(Id::U32(self.next_id), _token_uri);
-            self.set_token_location(Id::U32(self.next_id), _token_location);
-            self._mint_to(account, Id::U32(self.next_id));
-            self.next_id += 1;
-            Ok(())
-        }
+        #[ink::test]
+        fn redeem_voucher_mints_to_recipient() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+            let voucher = MintVoucher {
+                recipient: accounts.charlie,
+                token_uri: "uri".into(),
+                token_location: "loc".into(),
+                price: 100,
+                nonce: 7,
+                expiry: 1_000,
+            };
+            let (signer, signature) = sign_encoded([7u8; 32], &(contract, &voucher));
+            assert_eq!(PeerNftcontract.set_voucher_signer(signer, true), Ok(()));
 
-        /// Only manager
-        #[ink(message)]
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn manager_burn(
-            &mut self,
-            account: AccountId,
-            id: Id,
-            _token_location: String,
-            _token_uri: String,
-        ) -> Result<(), PSP34Error> {
-            self.remove_token_uri(id.clone());
-            self.remove_token_location(id.clone());
-            self._burn_from(account, id);
-            Ok(())
-        }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(
+                PeerNftcontract.redeem_voucher(voucher.clone(), signature),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(0)), Some(accounts.charlie));
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("uri"))
+            );
+            assert!(PeerNftcontract.is_voucher_nonce_used(7));
+            assert_eq!(PeerNftcontract.sale_proceeds(), 100);
 
-        /// Only manager
-        #[ink(message)]
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn manager_transfer(
-            &mut self,
-            to: AccountId,
-            id: Id,
-            _data: Vec<u8>,
-        ) -> Result<(), PSP34Error> {
-            self._transfer(to, id, _data)
+            assert_eq!(
+                PeerNftcontract.redeem_voucher(voucher, signature),
+                Err(PeerNftError::VoucherAlreadyUsed)
+            );
         }
 
-        /// Internal transfer function
-        fn _transfer(&mut self, to: AccountId, id: Id, _data: Vec<u8>) -> Result<(), PSP34Error> {
-            let owner = self._check_token_exists(&id)?;
-            let caller = self.env().caller();
+        #[ink::test]
+        fn presale_mint_requires_allowlist_proof() {
+            let mut PeerNftcontract =
+                PeerNftcontract::new("My First NFT".into(), "Nova".into());
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let (bob_leaf, charlie_leaf) =
+                (presale_leaf(&accounts.bob), presale_leaf(&accounts.charlie));
+            let root = hash_merkle_pair(bob_leaf, charlie_leaf);
 
-            self._before_token_transfer(Some(&owner), Some(&to), &id)?;
+            assert_eq!(PeerNftcontract.set_presale_merkle_root(Some(root)), Ok(()));
+            assert_eq!(PeerNftcontract.set_presale_quota(2), Ok(()));
+            assert_eq!(PeerNftcontract.set_mint_price(10), Ok(()));
 
-            self.psp34
-                .operator_approvals
-                .remove(&(&owner, &caller, &Some(&id)));
-            self.psp34.balances.decrease_balance(&owner, &id, false);
-            self.psp34.token_owner.remove(&id);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(20);
+            assert_eq!(
+                PeerNftcontract.presale_mint(vec![charlie_leaf], 2),
+                Err(PeerNftError::PresaleClosed)
+            );
 
-            self.psp34.balances.increase_balance(&to, &id, false);
-            self.psp34.token_owner.insert(&id, &to);
-            self._after_token_transfer(Some(&owner), Some(&to), &id)?;
-            self._emit_transfer_event(Some(owner), Some(to), id);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(PeerNftcontract.set_sale_phase(SalePhase::Presale), Ok(()));
 
-            Ok(())
-        }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert_eq!(
+                PeerNftcontract.presale_mint(vec![charlie_leaf], 2),
+                Err(PeerNftError::NotAllowlisted)
+            );
+            assert_eq!(
+                PeerNftcontract.mint(accounts.django, "loc".into(), "uri".into()),
+                Err(PeerNftError::NotMinter)
+            );
 
-        /// Modifies the code which is used to execute calls to this contract address (`AccountId`).
-        ///
-        /// We use this to upgrade the contract logic. We don't do any authorization here, any caller
-        /// can execute this method. In a production contract you would do some authorization here.
-        #[ink(message)]
-        #[openbrush::modifiers(only_role(MANAGER))]
-        pub fn set_code(&mut self, code_hash: [u8; 32]) -> Result<(), PSP34Error> {
-            ink::env::set_code_hash(&code_hash).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to `set_code_hash` to {:?} due to {:?}",
-                    code_hash, err
-                )
-            });
-            Ok(())
-        }
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.presale_mint(vec![charlie_leaf], 3),
+                Err(PeerNftError::MintLimitReached)
+            );
+            assert_eq!(PeerNftcontract.presale_mint(vec![charlie_leaf], 2), Ok(()));
+            assert_eq!(PeerNftcontract.owner_of(Id::U32(1)), Some(accounts.bob));
+            assert_eq!(PeerNftcontract.presale_mints_of(accounts.bob), 2);
+            assert_eq!(PeerNftcontract.get_token_uri(Id::U32(1)), None);
+            assert_eq!(PeerNftcontract.get_token_location(Id::U32(1)), None);
+            assert_eq!(PeerNftcontract.token_uri_history_len(Id::U32(1)), 0);
+            assert_eq!(PeerNftcontract.token_location_history_len(Id::U32(1)), 0);
+            assert_eq!(PeerNftcontract.sale_proceeds(), 20);
 
-        #[ink(message)]
-        pub fn nft_ids_of(&self, owner: AccountId) -> Option<Vec<u32>> {
-            let mut ids_vec: Vec<u32> = Vec::new();
-            for n in 0..self.next_id {
-                    let ids = Id::U32(n);
-                    let addr = self.owner_of(ids.clone()).unwrap();
-                    if owner == addr {
-                        ids_vec.push(n);
-                }
-            }
-            return Some(ids_vec);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(10);
+            assert_eq!(
+                PeerNftcontract.presale_mint(vec![charlie_leaf], 1),
+                Err(PeerNftError::MintLimitReached)
+            );
         }
-    }
-
-    ////////////////////////////////////////////// Off Chain Tests /////////////////////////////////////////
-
-    #[cfg(test)]
-    mod tests {
-        /// Imports all the definitions from the outer scope so we can use them here.
-        use super::*;
-        use ink::codegen::Env;
 
-        /// We test if the default constructor does its job.
         #[ink::test]
-        fn constructor_works_name() {
-            let PeerNftcontract =
+        fn location_attestation_rejects_stale_or_unsigned() {
+            let mut PeerNftcontract =
                 PeerNftcontract::new("My First NFT".into(), "Nova".into());
-            let collection_id = PeerNftcontract.collection_id();
-            let key = String::from("name");
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
 
             assert_eq!(
-                PeerNftcontract
-                    .get_attribute(collection_id, key)
-                    .unwrap(),
-                b"My First NFT"
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(
+                MAX_ATTESTATION_AGE + 10,
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.submit_location_attestation(
+                    Id::U32(0),
+                    "1.0,2.0".into(),
+                    MAX_ATTESTATION_AGE + 11,
+                    [0u8; 65]
+                ),
+                Err(PeerNftError::StaleAttestation)
+            );
+            assert_eq!(
+                PeerNftcontract.submit_location_attestation(
+                    Id::U32(0),
+                    "1.0,2.0".into(),
+                    9,
+                    [0u8; 65]
+                ),
+                Err(PeerNftError::StaleAttestation)
+            );
+            assert_eq!(
+                PeerNftcontract.submit_location_attestation(
+                    Id::U32(0),
+                    "1.0,2.0".into(),
+                    MAX_ATTESTATION_AGE,
+                    [0u8; 65]
+                ),
+                Err(PeerNftError::InvalidSignature)
+            );
+            assert_eq!(
+                PeerNftcontract.set_location_oracle([2u8; 33], true),
+                Err(PeerNftError::NotManager)
             );
+            assert_eq!(
+                PeerNftcontract.get_token_location(Id::U32(0)),
+                Some(String::from("loc"))
+            );
+            assert_eq!(PeerNftcontract.token_location_attestation(Id::U32(0)), None);
         }
 
         #[ink::test]
-        fn constructor_works_symbol() {
-            let PeerNftcontract =
+        fn location_attestation_records_oracle() {
+            let mut PeerNftcontract =
                 PeerNftcontract::new("My First NFT".into(), "Nova".into());
-            let collection_id = PeerNftcontract.collection_id();
-            let key = String::from("symbol");
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let contract = ink::env::test::callee::<ink::env::DefaultEnvironment>();
+
             assert_eq!(
-                PeerNftcontract
-                    .get_attribute(collection_id, key)
-                    .unwrap(),
-                "Nova".as_bytes()
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            let location = String::from("1.0,2.0");
+            let (oracle, signature) =
+                sign_encoded([9u8; 32], &(contract, &Id::U32(0), &location, 5u64));
+            assert_eq!(PeerNftcontract.set_location_oracle(oracle, true), Ok(()));
+
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.submit_location_attestation(
+                    Id::U32(0),
+                    location.clone(),
+                    5,
+                    signature
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                PeerNftcontract.get_token_location(Id::U32(0)),
+                Some(location.clone())
             );
+            assert_eq!(
+                PeerNftcontract.token_location_attestation(Id::U32(0)),
+                Some(LocationAttestation { oracle, timestamp: 5 })
+            );
+
+            let history = PeerNftcontract.token_location_history(Id::U32(0), 1, 1);
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].value, location);
+            assert_eq!(history[0].updater, PeerNftcontract::_oracle_account_id(&oracle));
+            assert_ne!(history[0].updater, accounts.bob);
         }
 
         #[ink::test]
-        fn check_admin_role() {
-            let PeerNftcontract =
+        fn geofences_apply_uri_on_location_change() {
+            let mut PeerNftcontract =
                 PeerNftcontract::new("My First NFT".into(), "Nova".into());
-            assert_eq!(PeerNftcontract.get_role_admin(0), 0);
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let sydney = GeofenceArea::Circle {
+                lat_e7: -338_568_000,
+                lng_e7: 1_512_153_000,
+                radius_m: 500,
+            };
+            let denmark = GeofenceArea::BoundingBox {
+                min_lat_e7: 545_000_000,
+                min_lng_e7: 80_000_000,
+                max_lat_e7: 578_000_000,
+                max_lng_e7: 130_000_000,
+            };
+
+            assert_eq!(
+                PeerNftcontract.add_geofence(
+                    GeofenceArea::Circle {
+                        lat_e7: 0,
+                        lng_e7: 0,
+                        radius_m: 0
+                    },
+                    "uri".into(),
+                    vec![]
+                ),
+                Err(PeerNftError::InvalidGeofence)
+            );
+            assert_eq!(
+                PeerNftcontract.add_geofence(
+                    sydney,
+                    "ipfs://sydney".into(),
+                    vec![("zone".into(), "sydney".into())]
+                ),
+                Ok(0)
+            );
+            assert_eq!(
+                PeerNftcontract.add_geofence(
+                    denmark.clone(),
+                    "ipfs://denmark".into(),
+                    vec![("zone".into(), vec![b'a'; MAX_GEOFENCE_ATTRIBUTE_LEN + 1])]
+                ),
+                Err(PeerNftError::InvalidGeofence)
+            );
+            assert_eq!(
+                PeerNftcontract.add_geofence(denmark, "ipfs://denmark".into(), vec![]),
+                Ok(1)
+            );
+
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "-33.8570,151.2150".into(), "uri".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.token_geofence(Id::U32(0)), Some(0));
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("ipfs://sydney"))
+            );
+            assert_eq!(
+                PeerNftcontract.get_attribute(Id::U32(0), String::from("zone")),
+                Some(b"sydney".to_vec())
+            );
+
+            // About 1km south of the centre, outside the 500m radius
+            let events_before = ink::env::test::recorded_events().count();
+            assert_eq!(
+                PeerNftcontract.update_token_location(Id::U32(0), "-33.8658,151.2153".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.token_geofence(Id::U32(0)), None);
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("ipfs://sydney"))
+            );
+            let events: Vec<_> = ink::env::test::recorded_events().collect();
+            assert!(events[events_before..].iter().any(|event| matches!(
+                <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap(),
+                Event::GeofenceExited(GeofenceExited { _fence_id: 0, .. })
+            )));
+
+            assert_eq!(
+                PeerNftcontract.update_token_location(Id::U32(0), "56.0,10.0".into()),
+                Ok(())
+            );
+            assert_eq!(PeerNftcontract.token_geofence(Id::U32(0)), Some(1));
+            assert_eq!(
+                PeerNftcontract.get_token_uri(Id::U32(0)),
+                Some(String::from("ipfs://denmark"))
+            );
+
+            assert_eq!(PeerNftcontract.remove_geofence(1), Ok(()));
+            assert_eq!(
+                PeerNftcontract.remove_geofence(1),
+                Err(PeerNftError::GeofenceNotFound)
+            );
+            assert_eq!(PeerNftcontract.geofence_ids(), vec![0]);
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.remove_geofence(0),
+                Err(PeerNftError::NotManager)
+            );
         }
 
         #[ink::test]
-        fn check_what_admin_role_contains() {
-            let PeerNftcontract =
-                PeerNftcontract::new("My First NFT".into(), "Nova".into());
-            assert_eq!(PeerNftcontract.access.admin_roles.get(0), None);
+        fn geofence_area_contains() {
+            let circle = GeofenceArea::Circle {
+                lat_e7: 600_000_000,
+                lng_e7: 1_799_990_000,
+                radius_m: 1_000,
+            };
+            // 0.016 degrees of longitude across the antimeridian is about 890m at 60 degrees north
+            assert!(circle.contains(600_000_000, -1_799_850_000));
+            assert!(!circle.contains(600_000_000, -1_799_750_000));
+
+            let across_antimeridian = GeofenceArea::BoundingBox {
+                min_lat_e7: -100_000_000,
+                min_lng_e7: 1_700_000_000,
+                max_lat_e7: 100_000_000,
+                max_lng_e7: -1_700_000_000,
+            };
+            assert!(across_antimeridian.contains(0, 1_750_000_000));
+            assert!(across_antimeridian.contains(0, -1_750_000_000));
+            assert!(!across_antimeridian.contains(0, 0));
+            assert!(!across_antimeridian.contains(200_000_000, 1_750_000_000));
         }
 
         #[ink::test]
-        fn check_do_admin_role_contains() {
-            let PeerNftcontract =
+        fn update_token_uri_errors() {
+            let mut PeerNftcontract =
                 PeerNftcontract::new("My First NFT".into(), "Nova".into());
-            assert_eq!(PeerNftcontract.access.admin_roles.contains(0), false);
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+
+            assert_eq!(
+                PeerNftcontract.update_token_uri(Id::U32(0), "uri".into()),
+                Err(PeerNftError::TokenNotFound)
+            );
+            assert_eq!(
+                PeerNftcontract.mint(accounts.bob, "loc".into(), "uri".into()),
+                Ok(())
+            );
+            let long_uri = vec![b'a'; MAX_TOKEN_URI_LEN + 1];
+            assert_eq!(
+                PeerNftcontract.update_token_uri(Id::U32(0), long_uri),
+                Err(PeerNftError::UriTooLong)
+            );
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                PeerNftcontract.update_token_uri(Id::U32(0), "new uri".into()),
+                Err(PeerNftError::NotManager)
+            );
         }
 
         // #[ink::test]